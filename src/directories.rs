@@ -1,36 +1,68 @@
+use crate::error::Error;
+use crate::releases::{is_foreign_platform, platform};
 use crate::version::Version;
 
-fn lilyenv_dir() -> directories::ProjectDirs {
-    directories::ProjectDirs::from("", "", "Lilyenv").expect("Could not find the home directory")
+fn lilyenv_dir() -> Result<directories::ProjectDirs, Error> {
+    directories::ProjectDirs::from("", "", "Lilyenv").ok_or(Error::NoHomeDirectory)
 }
 
-pub fn downloads_dir() -> std::path::PathBuf {
-    lilyenv_dir().cache_dir().join("downloads")
+pub fn downloads_dir() -> Result<std::path::PathBuf, Error> {
+    Ok(lilyenv_dir()?.cache_dir().join("downloads"))
 }
 
-pub fn python_dir(version: &Version) -> std::path::PathBuf {
-    lilyenv_dir()
-        .data_local_dir()
-        .join("pythons")
-        .join(version.to_string())
+pub fn releases_cache_dir() -> Result<std::path::PathBuf, Error> {
+    Ok(lilyenv_dir()?.cache_dir().join("releases"))
 }
 
-pub fn virtualenvs_dir() -> std::path::PathBuf {
-    lilyenv_dir().data_local_dir().join("virtualenvs")
+pub fn pythons_dir() -> Result<std::path::PathBuf, Error> {
+    let pythons = lilyenv_dir()?.data_local_dir().join("pythons");
+    // Keep interpreters for other platforms apart from the ones this machine can run.
+    match is_foreign_platform() {
+        true => Ok(pythons.join(platform())),
+        false => Ok(pythons),
+    }
 }
 
-pub fn shell_file() -> std::path::PathBuf {
-    lilyenv_dir().data_local_dir().join("shell")
+pub fn python_dir(version: &Version) -> Result<std::path::PathBuf, Error> {
+    Ok(pythons_dir()?.join(version.to_string()))
 }
 
-pub fn project_dir(project: &str) -> std::path::PathBuf {
-    virtualenvs_dir().join(project)
+pub fn virtualenvs_dir() -> Result<std::path::PathBuf, Error> {
+    Ok(lilyenv_dir()?.data_local_dir().join("virtualenvs"))
 }
 
-pub fn virtualenv_dir(project: &str, version: &Version) -> std::path::PathBuf {
-    project_dir(project).join(version.to_string())
+pub fn shell_file() -> Result<std::path::PathBuf, Error> {
+    Ok(lilyenv_dir()?.data_local_dir().join("shell"))
 }
 
-pub fn project_file(project: &str) -> std::path::PathBuf {
-    project_dir(project).join("directory")
+pub fn aliases_file() -> Result<std::path::PathBuf, Error> {
+    Ok(lilyenv_dir()?.data_local_dir().join("aliases"))
+}
+
+pub fn project_dir(project: &str) -> Result<std::path::PathBuf, Error> {
+    Ok(virtualenvs_dir()?.join(project))
+}
+
+pub fn virtualenv_dir(project: &str, version: &Version) -> Result<std::path::PathBuf, Error> {
+    Ok(project_dir(project)?.join(version.to_string()))
+}
+
+pub fn project_file(project: &str) -> Result<std::path::PathBuf, Error> {
+    Ok(project_dir(project)?.join("directory"))
+}
+
+pub fn hook_file(project: &str) -> Result<std::path::PathBuf, Error> {
+    Ok(project_dir(project)?.join("hooks").join("activate"))
+}
+
+pub fn interpreter_file(project: &str, version: &Version) -> Result<std::path::PathBuf, Error> {
+    Ok(virtualenv_dir(project, version)?.join("interpreter"))
+}
+
+pub fn metadata_file(project: &str, version: &Version) -> Result<std::path::PathBuf, Error> {
+    Ok(virtualenv_dir(project, version)?.join(".lilyenv-meta"))
+}
+
+pub fn freeze_file(project: &str, version: &Version) -> Result<std::path::PathBuf, Error> {
+    Ok(project_dir(project)?.join(format!("requirements-{version}.txt")))
 }
@@ -1,172 +1,882 @@
 use crate::directories::{downloads_dir, python_dir};
 use crate::error::Error;
-use crate::releases::{cpython_releases, pypy_releases};
-use crate::version::{Interpreter, Version};
+use crate::http::{download_client, send};
+use crate::releases::{cpython_releases, is_foreign_platform, platform, pypy_releases, Python};
+use crate::version::{pypy_release_key, Interpreter, PreRelease, Version};
 use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
+use log::{debug, info, trace, warn};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
 use tar::Archive;
 use url::Url;
+use xz2::read::XzDecoder;
 use zstd::stream::read::Decoder as ZstDecoder;
 
-pub fn download_python(version: &Version, upgrade: bool) -> Result<(), Error> {
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DownloadOptions {
+    /// Replace an existing interpreter with the latest compatible release.
+    pub upgrade: bool,
+    /// Remove an existing interpreter and download it again from scratch.
+    pub force: bool,
+    /// Don't report which file is being downloaded.
+    pub quiet: bool,
+    /// Allow alpha, beta and release candidate builds.
+    pub pre: bool,
+    /// Download the `full` archive, with build artifacts and tests, rather than `install_only`.
+    pub full: bool,
+}
+
+/// Called with the bytes downloaded so far and the total size, if known, as a file downloads.
+pub type Progress<'a> = &'a dyn Fn(u64, Option<u64>);
+
+/// Download an interpreter, returning the directory it is installed in.
+pub fn download_python(version: &Version, options: DownloadOptions) -> Result<PathBuf, Error> {
+    download_python_with_progress(version, options, &|_, _| {})
+}
+
+/// Like `download_python`, calling `progress` as the interpreter downloads.
+pub fn download_python_with_progress(
+    version: &Version,
+    options: DownloadOptions,
+    progress: Progress,
+) -> Result<PathBuf, Error> {
+    let python_dir = python_dir(version)?;
     match version.interpreter {
-        Interpreter::CPython => download_cpython(version, upgrade),
-        Interpreter::PyPy => download_pypy(version, upgrade),
+        Interpreter::CPython => download_cpython(version, &python_dir, options, progress)?,
+        Interpreter::PyPy => download_pypy(version, &python_dir, options, progress)?,
     }
+    Ok(python_dir.join("python"))
 }
 
-pub fn print_available_downloads() -> Result<(), Error> {
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()?;
-    let mut releases = rt.block_on(cpython_releases())?;
-    releases.sort_unstable_by_key(|p| p.version);
+/// Download an interpreter into `target` instead of lilyenv's own directory, returning the
+/// directory it is installed in.
+///
+/// The interpreter's paths are rewritten for `target`, so it can only be moved somewhere with
+/// the same absolute path, such as into a container image. `target` must be empty or hold an
+/// interpreter lilyenv installed, and only that interpreter is ever replaced.
+pub fn extract_python(
+    version: &Version,
+    options: DownloadOptions,
+    target: &Path,
+) -> Result<PathBuf, Error> {
+    let target = std::path::absolute(target)?;
+    if !is_extract_target(&target)? {
+        return Err(Error::ExtractTarget(target));
+    }
+    std::fs::create_dir_all(&target)?;
+    let target = std::fs::canonicalize(&target)?;
+    warn_foreign_platform();
+    match version.interpreter {
+        Interpreter::CPython => download_cpython(version, &target, options, &|_, _| {})?,
+        Interpreter::PyPy => download_pypy(version, &target, options, &|_, _| {})?,
+    }
+    Ok(target.join("python"))
+}
+
+fn warn_foreign_platform() {
+    if is_foreign_platform() {
+        warn!(
+            "Downloading for {} only: these interpreters can't run on this machine, so no virtualenvs can be made with them.",
+            platform()
+        );
+    }
+}
+
+/// Whether `target` is missing, empty or holds an interpreter lilyenv installed.
+fn is_extract_target(target: &Path) -> Result<bool, Error> {
+    match target.read_dir() {
+        Ok(mut entries) => Ok(entries.next().is_none() || target.join(COMPLETE_MARKER).is_file()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(true),
+        Err(err) => Err(err)?,
+    }
+}
+
+/// Download several versions, running up to `jobs` downloads at once, and return the
+/// directories they are installed in.
+pub fn download_pythons(
+    versions: &[Version],
+    options: DownloadOptions,
+    keep_going: bool,
+    jobs: usize,
+) -> Result<Vec<PathBuf>, Error> {
+    warn_foreign_platform();
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let results = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(versions.len()) {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(version) = versions.get(index) else {
+                        break;
+                    };
+                    let result = download_python(version, options);
+                    if let Err(err) = &result {
+                        match keep_going {
+                            true => eprintln!("Could not download {version}: {err}"),
+                            false => stop.store(true, Ordering::Relaxed),
+                        }
+                    }
+                    results
+                        .lock()
+                        .expect("A download thread panicked.")
+                        .push((index, result));
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().expect("A download thread panicked.");
+    results.sort_by_key(|(index, _)| *index);
+    let mut failed = vec![];
+    let mut installed = vec![];
+    for (index, result) in results {
+        match result {
+            Ok(path) => installed.push(path),
+            Err(_) if keep_going => failed.push(versions[index].to_string()),
+            Err(err) => return Err(err),
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(installed),
+        false => Err(Error::DownloadsFailed(failed.join(", "))),
+    }
+}
+
+/// Whether a release matches every term of a search query, ignoring case.
+///
+/// Terms are matched against the listed `version (release_tag)` along with the interpreter
+/// name and build variants, so `3.12 debug` finds the debug builds of 3.12.
+fn matches_query(python: &Python, terms: &[String]) -> bool {
+    let interpreter = match python.version.interpreter {
+        Interpreter::CPython => "cpython",
+        Interpreter::PyPy => "pypy",
+    };
+    let mut text = format!("{interpreter} {} ({})", python.version, python.release_tag);
+    if python.version.freethreaded {
+        text += " freethreaded";
+    }
+    text.make_ascii_lowercase();
+    terms
+        .iter()
+        .all(|term| text.contains(&term.to_ascii_lowercase()))
+}
+
+/// Keep only the newest release of each `major.minor` version and build variant: the
+/// highest bugfix, final releases over prereleases, and then the newest release tag.
+fn latest_releases(releases: Vec<Python>) -> Vec<Python> {
+    let key = |python: &Python| {
+        (
+            python.version.bugfix,
+            prerelease_rank(python.version.prerelease),
+            pypy_release_key(&python.release_tag),
+        )
+    };
+    let mut latest = std::collections::BTreeMap::new();
     for python in releases {
-        println!("{} ({})", python.version, python.release_tag);
+        let version = python.version;
+        let line = (
+            version.interpreter,
+            version.major,
+            version.minor,
+            version.debug,
+            version.freethreaded,
+        );
+        match latest.get(&line) {
+            Some(newest) if key(newest) >= key(&python) => {}
+            _ => {
+                latest.insert(line, python);
+            }
+        }
+    }
+    latest.into_values().collect()
+}
+
+pub fn print_available_downloads(query: &[String], latest_only: bool) -> Result<(), Error> {
+    let matches = |python: &Python| matches_query(python, query);
+    for mut releases in [cpython_releases()?, pypy_releases()?] {
+        if latest_only {
+            releases = latest_releases(releases);
+        }
+        releases.sort_unstable_by_key(|p| p.version);
+        for python in releases.into_iter().filter(matches) {
+            println!("{} ({})", python.version, python.release_tag);
+        }
     }
+    Ok(())
+}
+
+fn python_json(python: &Python) -> serde_json::Value {
+    let interpreter = match python.version.interpreter {
+        Interpreter::CPython => "cpython",
+        Interpreter::PyPy => "pypy",
+    };
+    serde_json::json!({
+        "interpreter": interpreter,
+        "version": python.version.to_string(),
+        "release_tag": python.release_tag,
+        "debug": python.version.debug,
+        "freethreaded": python.version.freethreaded,
+        "url": python.url.as_str(),
+    })
+}
+
+/// Print the available downloads as a JSON array, for use by other tools.
+pub fn print_available_downloads_json() -> Result<(), Error> {
+    let mut releases = cpython_releases()?;
+    releases.sort_unstable_by_key(|p| p.version);
     let mut pypy_releases = pypy_releases()?;
     pypy_releases.sort_unstable_by_key(|p| p.version);
-    for python in pypy_releases {
-        println!("{} ({})", python.version, python.release_tag);
+    let releases = releases
+        .iter()
+        .chain(&pypy_releases)
+        .map(python_json)
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::Value::Array(releases));
+    Ok(())
+}
+
+/// The distinct `major.minor` versions among `releases`, in ascending order.
+fn minor_versions(releases: &[Python]) -> Vec<Version> {
+    releases
+        .iter()
+        .map(|python| Version {
+            bugfix: None,
+            debug: false,
+            freethreaded: false,
+            prerelease: PreRelease::None,
+            ..python.version
+        })
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+pub fn print_available_versions(interpreter: Option<Interpreter>) -> Result<(), Error> {
+    let show = |shown: Interpreter| interpreter.is_none_or(|interpreter| interpreter == shown);
+    for (name, shown, releases) in [
+        (
+            "cpython",
+            Interpreter::CPython,
+            cpython_releases as fn() -> _,
+        ),
+        ("pypy", Interpreter::PyPy, pypy_releases),
+    ] {
+        if show(shown) {
+            let versions = minor_versions(&releases()?)
+                .iter()
+                .map(Version::to_string)
+                .collect::<Vec<_>>();
+            println!("{name}: {}", versions.join(" "));
+        }
     }
     Ok(())
 }
 
-fn download_cpython(version: &Version, upgrade: bool) -> Result<(), Error> {
-    let python_dir = python_dir(version);
-    if !upgrade && python_dir.exists() {
+/// Written to a python directory, containing the release tag, once it's fully set up.
+const COMPLETE_MARKER: &str = ".lilyenv-complete";
+
+/// The interpreter executable inside a python directory.
+pub fn python_executable(python_dir: &Path) -> PathBuf {
+    python_dir.join("python").join("bin").join("python3")
+}
+
+/// Whether `python_dir` holds a complete interpreter, rather than nothing or a partial extraction.
+pub fn is_downloaded(python_dir: &Path) -> bool {
+    python_dir.join(COMPLETE_MARKER).is_file() && python_executable(python_dir).is_file()
+}
+
+/// The exact interpreter build in a python directory, as recorded in its completion marker.
+#[derive(Debug, PartialEq)]
+pub struct Build {
+    pub release_tag: String,
+    /// Missing for interpreters downloaded by older versions of lilyenv.
+    pub version: Option<Version>,
+}
+
+impl std::fmt::Display for Build {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.version {
+            Some(version) => write!(f, "{version} ({})", self.release_tag),
+            None => write!(f, "{}", self.release_tag),
+        }
+    }
+}
+
+fn write_complete_marker(
+    python_dir: &Path,
+    release_tag: &str,
+    version: &Version,
+) -> Result<(), Error> {
+    let marker = format!("{release_tag}\n{version}\n");
+    std::fs::write(python_dir.join(COMPLETE_MARKER), marker)?;
+    Ok(())
+}
+
+fn parse_complete_marker(marker: &str) -> Build {
+    let mut lines = marker.lines();
+    Build {
+        release_tag: lines.next().unwrap_or_default().trim().to_string(),
+        version: lines.next().and_then(|version| version.trim().parse().ok()),
+    }
+}
+
+/// The build of the interpreter downloaded to `python_dir`, if it is complete.
+pub fn downloaded_build(python_dir: &Path) -> Option<Build> {
+    let marker = std::fs::read_to_string(python_dir.join(COMPLETE_MARKER)).ok()?;
+    Some(parse_complete_marker(&marker))
+}
+
+/// Remove any partial or unwanted interpreter from `python_dir`, returning whether a download
+/// is needed.
+fn prepare_python_dir(python_dir: &Path, options: DownloadOptions) -> Result<bool, Error> {
+    let downloaded = is_downloaded(python_dir);
+    if downloaded && !options.upgrade && !options.force {
+        info!("Already downloaded to {}", python_dir.display());
+        return Ok(false);
+    }
+    if options.force || !downloaded {
+        remove_install(python_dir)?;
+    }
+    Ok(true)
+}
+
+/// Remove the interpreter and completion marker from `python_dir`, leaving anything else.
+fn remove_install(python_dir: &Path) -> Result<(), Error> {
+    let python = python_dir.join("python");
+    if python.exists() {
+        info!("Removing {}", python.display());
+        std::fs::remove_dir_all(python)?;
+    }
+    let marker = python_dir.join(COMPLETE_MARKER);
+    if marker.exists() {
+        std::fs::remove_file(marker)?;
+    }
+    Ok(())
+}
+
+/// Order prereleases before the final release they lead up to.
+fn prerelease_rank(prerelease: PreRelease) -> (u8, u8) {
+    match prerelease {
+        PreRelease::Alpha(n) => (0, n),
+        PreRelease::Beta(n) => (1, n),
+        PreRelease::RC(n) => (2, n),
+        PreRelease::None => (3, 0),
+    }
+}
+
+/// Archive variants in order of preference, when a build is published as several.
+/// `install_only` archives are smallest and already laid out for use.
+const ARCHIVE_PREFERENCE: [&str; 4] = [
+    "-install_only.",
+    "-install_only_stripped.",
+    "pgo+lto-full.",
+    "-full.",
+];
+
+/// How much an asset's archive variant is preferred, higher being better.
+fn archive_preference(name: &str) -> usize {
+    match ARCHIVE_PREFERENCE
+        .iter()
+        .position(|variant| name.contains(variant))
+    {
+        Some(position) => ARCHIVE_PREFERENCE.len() - position,
+        None => 0,
+    }
+}
+
+/// Pick the newest release compatible with `version`, so `3.12` gets the latest `3.12.z`.
+/// With `pre`, alpha, beta and release candidate builds of `version` are candidates too.
+/// With `full`, only `full` archives are candidates.
+///
+/// Among equally new assets the preferred archive variant wins, and then the first listed.
+fn select_cpython(
+    releases: Vec<Python>,
+    version: &Version,
+    pre: bool,
+    full: bool,
+) -> Option<Python> {
+    releases
+        .into_iter()
+        // `compatible` checks these too, but a debug or free-threaded build must never stand
+        // in for the plain build (or the other way around), so make it explicit.
+        .filter(|python| {
+            python.version.debug == version.debug
+                && python.version.freethreaded == version.freethreaded
+        })
+        .filter(|python| !full || python.name.contains("-full."))
+        .filter(|python| {
+            let release = Version {
+                prerelease: PreRelease::None,
+                ..python.version
+            };
+            python.version.compatible(version) || (pre && release.compatible(version))
+        })
+        .min_by_key(|python| {
+            std::cmp::Reverse((
+                python.version.bugfix,
+                prerelease_rank(python.version.prerelease),
+                python.release_tag.clone(),
+                archive_preference(&python.name),
+            ))
+        })
+}
+
+/// Pick the newest PyPy release compatible with `version`.
+fn select_pypy(releases: Vec<Python>, version: &Version) -> Option<Python> {
+    releases
+        .into_iter()
+        .filter(|python| python.version.compatible(version))
+        .max_by_key(|python| pypy_release_key(&python.release_tag))
+}
+
+/// The exact version that downloading `version` would install, e.g. `3.12.8` for `3.12`.
+pub fn latest_bugfix(version: &Version) -> Result<Version, Error> {
+    if version.bugfix.is_some() {
+        return Ok(*version);
+    }
+    let python = match version.interpreter {
+        Interpreter::CPython => select_cpython(cpython_releases()?, version, false, false),
+        Interpreter::PyPy => select_pypy(pypy_releases()?, version),
+    };
+    match python {
+        Some(python) => Ok(python.version),
+        None => Err(Error::VersionNotFound(version.to_string())),
+    }
+}
+
+fn download_cpython(
+    version: &Version,
+    python_dir: &Path,
+    options: DownloadOptions,
+    progress: Progress,
+) -> Result<(), Error> {
+    if !prepare_python_dir(python_dir, options)? {
         return Ok(());
     }
 
-    let downloads = downloads_dir();
+    let downloads = downloads_dir()?;
     std::fs::create_dir_all(&downloads)?;
 
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()?;
-    let python = match rt
-        .block_on(cpython_releases())?
-        .into_iter()
-        .find(|python| python.version.compatible(version))
-    {
+    let python = match select_cpython(cpython_releases()?, version, options.pre, options.full) {
         Some(python) => python,
         None => {
             return Err(Error::VersionNotFound(version.to_string()));
         }
     };
+    info!("Selected {} ({})", python.name, python.release_tag);
     let path = downloads.join(python.name);
-    if upgrade || !path.exists() {
-        download_file(python.url, &path)?;
-    }
-    match python.debug {
-        false => extract_tar_gz(&path, &python_dir)?,
-        true => {
-            extract_tar_zst(&path, &python_dir)?;
-            move_install(&python_dir)?;
-        }
-    };
-    fixup_sysconfig_paths(&python_dir)?;
-    Ok(())
+    fetch_archive(python.url, &path, options, progress)?;
+    install_python(python_dir, |staging| {
+        extract(&path, staging, options.quiet)?;
+        move_install(staging)?;
+        fixup_sysconfig_paths(staging, python_dir)?;
+        write_complete_marker(staging, &python.release_tag, &python.version)
+    })
 }
 
-fn download_pypy(version: &Version, upgrade: bool) -> Result<(), Error> {
-    let python_dir = python_dir(version);
-    if !upgrade && python_dir.exists() {
+fn download_pypy(
+    version: &Version,
+    python_dir: &Path,
+    options: DownloadOptions,
+    progress: Progress,
+) -> Result<(), Error> {
+    if !prepare_python_dir(python_dir, options)? {
         return Ok(());
     }
 
-    let downloads = downloads_dir();
+    let downloads = downloads_dir()?;
     std::fs::create_dir_all(&downloads)?;
 
-    let python = match pypy_releases()?
-        .into_iter()
-        .find(|python| python.version.compatible(version))
-    {
+    let python = match select_pypy(pypy_releases()?, version) {
         Some(python) => python,
         None => {
             return Err(Error::VersionNotFound(version.to_string()));
         }
     };
-    let path = downloads.join(python.name);
-    if upgrade || !path.exists() {
-        download_file(python.url, &path)?;
+    info!("Selected {} ({})", python.name, python.release_tag);
+    let path = downloads.join(&python.name);
+    fetch_archive(python.url, &path, options, progress)?;
+    install_python(python_dir, |staging| {
+        extract(&path, staging, options.quiet)?;
+        // PyPy archives contain a directory named after the archive, so rename it to match the
+        // `python` directory of CPython builds.
+        let extracted = staging.join(python.name.strip_suffix(".tar.bz2").unwrap_or(&python.name));
+        std::fs::rename(extracted, staging.join("python"))?;
+        write_complete_marker(staging, &python.release_tag, &python.version)
+    })
+}
+
+/// Download an archive to `path`, unless it is already there and may be reused.
+fn fetch_archive(
+    url: Url,
+    path: &Path,
+    options: DownloadOptions,
+    progress: Progress,
+) -> Result<(), Error> {
+    if options.upgrade || options.force || !path.exists() {
+        let part = part_file(path);
+        if options.force && part.exists() {
+            info!("Removing {}", part.display());
+            std::fs::remove_file(part)?;
+        }
+        download_file(url, path, options.quiet, progress)
+    } else {
+        info!("Using cached {}", path.display());
+        Ok(())
+    }
+}
+
+/// The directory next to `python_dir` that a new interpreter is set up in.
+fn staging_dir(python_dir: &Path) -> PathBuf {
+    let mut name = python_dir
+        .file_name()
+        .expect("A python directory has a file name.")
+        .to_os_string();
+    name.push(".partial");
+    python_dir.with_file_name(name)
+}
+
+/// Set up an interpreter in a staging directory with `setup`, then move it to `python_dir`.
+///
+/// If `setup` fails the staging directory is removed, so `python_dir` never holds a
+/// partially extracted interpreter.
+fn install_python(
+    python_dir: &Path,
+    setup: impl FnOnce(&Path) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let staging = staging_dir(python_dir);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    if let Err(err) = setup(&staging) {
+        debug!("Removing {}", staging.display());
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(err);
+    }
+    remove_install(python_dir)?;
+    std::fs::create_dir_all(python_dir)?;
+    debug!("Moving {} to {}", staging.display(), python_dir.display());
+    for name in ["python", COMPLETE_MARKER] {
+        std::fs::rename(staging.join(name), python_dir.join(name))?;
+    }
+    std::fs::remove_dir_all(&staging)?;
+    Ok(())
+}
+
+/// How many times to try downloading a file before giving up.
+const DOWNLOAD_ATTEMPTS: u32 = 4;
+/// The delay before the first retry, doubled for each retry after that.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Download `url` to `target`, retrying transient failures with exponential backoff.
+///
+/// The file is written to a `.part` file next to `target` first, so that retries and
+/// later runs can resume an interrupted download instead of starting again.
+fn download_file(url: Url, target: &Path, quiet: bool, progress: Progress) -> Result<(), Error> {
+    let part = part_file(target);
+    let mut attempt = 1;
+    loop {
+        match download_attempt(&url, &part, quiet, progress) {
+            Ok(()) => break,
+            Err(err) if attempt < DOWNLOAD_ATTEMPTS && is_transient(&err) => {
+                let delay = RETRY_DELAY * 2u32.pow(attempt - 1);
+                warn!(
+                    "Downloading {url} failed: {err}. Retrying in {} seconds.",
+                    delay.as_secs()
+                );
+                std::thread::sleep(delay);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    std::fs::rename(&part, target)?;
+    Ok(())
+}
+
+fn part_file(target: &Path) -> PathBuf {
+    let mut name = target
+        .file_name()
+        .expect("A download target is a file.")
+        .to_os_string();
+    name.push(".part");
+    target.with_file_name(name)
+}
+
+/// The file size in the `Content-Range` header of a 416 response, like `bytes */1234`.
+fn unsatisfied_range_size(content_range: &str) -> Option<u64> {
+    content_range.strip_prefix("bytes */")?.trim().parse().ok()
+}
+
+/// Whether a failed download is worth retrying.
+fn is_transient(err: &Error) -> bool {
+    match err {
+        Error::Timeout(_) | Error::Proxy(_, _) | Error::Unreachable(_, _) => true,
+        Error::Request(err) => {
+            err.is_connect()
+                || err.is_timeout()
+                || err.is_body()
+                || err.status().is_some_and(|status| status.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+fn download_attempt(url: &Url, part: &Path, quiet: bool, progress: Progress) -> Result<(), Error> {
+    let offset = match std::fs::metadata(part) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(err) => Err(err)?,
+    };
+    let mut request = download_client()?.get(url.clone());
+    if offset > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let response = send(request)?;
+    if offset > 0 && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The `.part` file was complete but not renamed, or doesn't match the file any more.
+        let size = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(unsatisfied_range_size);
+        if size == Some(offset) {
+            info!("{} is already complete", part.display());
+            return Ok(());
+        }
+        info!("Could not resume {}, so starting again", part.display());
+        std::fs::remove_file(part)?;
+        return download_attempt(url, part, quiet, progress);
     }
-    extract_tar_bz2(&path, &python_dir)?;
+    let mut response = response.error_for_status()?;
+    // Servers that ignore the range send the whole file, so start again.
+    let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let offset = if resumed { offset } else { 0 };
+    if !quiet {
+        let size = match response.content_length() {
+            Some(size) => format!("{:.1} MB", (offset + size) as f64 / 1_000_000.0),
+            None => "unknown size".to_string(),
+        };
+        let name = part
+            .file_stem()
+            .expect("A download target is a file.")
+            .to_string_lossy();
+        match resumed {
+            true => eprintln!(
+                "Resuming {name} ({size}) from {url} at {:.1} MB",
+                offset as f64 / 1_000_000.0
+            ),
+            false => eprintln!("Downloading {name} ({size}) from {url}"),
+        }
+    }
+    let file = match resumed {
+        true => std::fs::OpenOptions::new()
+            .append(true)
+            .open(part)
+            .map_err(|err| Error::CreateFile(part.to_path_buf(), err))?,
+        false => create_file(part)?,
+    };
+    let mut writer = ProgressWriter {
+        inner: file,
+        written: offset,
+        total: response.content_length().map(|size| offset + size),
+        progress,
+    };
+    progress(writer.written, writer.total);
+    response.copy_to(&mut writer)?;
     Ok(())
 }
 
-fn download_file(url: Url, target: &Path) -> Result<(), Error> {
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("lilyenv")
-        .build()?;
-    let response = client.get(url).send()?;
-    let mut file = File::create(target)?;
-    let mut content = std::io::Cursor::new(response.bytes()?);
-    std::io::copy(&mut content, &mut file)?;
+/// Reports the progress of a download as it is written.
+struct ProgressWriter<'a, W> {
+    inner: W,
+    written: u64,
+    total: Option<u64>,
+    progress: Progress<'a>,
+}
+
+impl<W: std::io::Write> std::io::Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        (self.progress)(self.written, self.total);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Create `target`, re-creating its directory and retrying once if it went missing.
+fn create_file(target: &Path) -> Result<File, Error> {
+    let err = match File::create(target) {
+        Ok(file) => return Ok(file),
+        Err(err) => err,
+    };
+    let parent = match (err.kind(), target.parent()) {
+        (std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied, Some(parent)) => {
+            parent
+        }
+        _ => return Err(Error::CreateFile(target.to_path_buf(), err)),
+    };
+    std::fs::create_dir_all(parent).map_err(|err| Error::CreateFile(parent.to_path_buf(), err))?;
+    File::create(target).map_err(|err| Error::CreateFile(target.to_path_buf(), err))
+}
+
+/// Extract an archive, choosing the decompression from its file extension.
+fn extract(source: &Path, target: &Path, quiet: bool) -> Result<(), Error> {
+    let name = source
+        .file_name()
+        .expect("An archive path has a file name.")
+        .to_string_lossy();
+    if !quiet {
+        eprintln!("Extracting {name}");
+    }
+    info!("Extracting {} to {}", source.display(), target.display());
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        extract_tar_gz(source, target, quiet)?;
+    } else if name.ends_with(".tar.zst") {
+        extract_tar_zst(source, target, quiet)?;
+    } else if name.ends_with(".tar.bz2") {
+        extract_tar_bz2(source, target, quiet)?;
+    } else if name.ends_with(".tar.xz") {
+        extract_tar_xz(source, target, quiet)?;
+    } else {
+        return Err(Error::UnsupportedArchive(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Check that an archive entry's path stays inside the directory it is extracted to.
+fn is_safe_entry_path(path: &Path) -> bool {
+    path.components()
+        .all(|part| matches!(part, Component::Normal(_) | Component::CurDir))
+}
+
+/// How many entries to unpack between updates of the extraction status.
+const STATUS_INTERVAL: usize = 100;
+
+/// Unpack an archive keeping the executable bits and symlinks of the interpreter layout.
+/// Unless `quiet`, a count of the files extracted so far is shown in a terminal.
+///
+/// Entries that would be written outside `target` are rejected rather than skipped.
+fn unpack<R: std::io::Read>(
+    mut archive: Archive<R>,
+    target: &Path,
+    quiet: bool,
+) -> Result<(), Error> {
+    use std::io::IsTerminal;
+    let show_status = !quiet && std::io::stderr().is_terminal();
+    let mut count = 0;
+    let mut extracted = |path: &Path| {
+        trace!("Extracted {}", path.display());
+        count += 1;
+        if show_status && count % STATUS_INTERVAL == 0 {
+            eprint!("\r{count} files extracted");
+        }
+    };
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(false);
+    archive.set_overwrite(true);
+    std::fs::create_dir_all(target)?;
+    let target = target.canonicalize()?;
+    // Like `Archive::unpack`, create directories last so their permissions
+    // don't prevent extracting their contents.
+    let mut directories = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        if !is_safe_entry_path(&path) {
+            return Err(Error::UnsafeArchivePath(path.display().to_string()));
+        }
+        if entry.header().entry_type() == tar::EntryType::Directory {
+            directories.push(entry);
+        } else {
+            let path = path.to_path_buf();
+            unpack_entry(entry, &target)?;
+            extracted(&path);
+        }
+    }
+    for entry in directories {
+        let path = entry.path()?.to_path_buf();
+        unpack_entry(entry, &target)?;
+        extracted(&path);
+    }
+    if show_status {
+        eprintln!("\r{count} files extracted");
+    }
+    Ok(())
+}
+
+fn unpack_entry<R: std::io::Read>(mut entry: tar::Entry<R>, target: &Path) -> Result<(), Error> {
+    if !entry.unpack_in(target)? {
+        let path = entry.path()?.display().to_string();
+        return Err(Error::UnsafeArchivePath(path));
+    }
     Ok(())
 }
 
-fn extract_tar_gz(source: &Path, target: &Path) -> Result<(), std::io::Error> {
+fn extract_tar_gz(source: &Path, target: &Path, quiet: bool) -> Result<(), Error> {
     let tar_gz = File::open(source)?;
     let tar = GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-    archive.unpack(target)?;
-    Ok(())
+    unpack(Archive::new(tar), target, quiet)
 }
 
-fn extract_tar_zst(source: &Path, target: &Path) -> Result<(), std::io::Error> {
+fn extract_tar_zst(source: &Path, target: &Path, quiet: bool) -> Result<(), Error> {
     let tar_zst = File::open(source)?;
     let tar = ZstDecoder::new(tar_zst)?;
-    let mut archive = Archive::new(tar);
-    archive.unpack(target)?;
-    Ok(())
+    unpack(Archive::new(tar), target, quiet)
 }
 
-fn extract_tar_bz2(source: &Path, target: &Path) -> Result<(), std::io::Error> {
+fn extract_tar_bz2(source: &Path, target: &Path, quiet: bool) -> Result<(), Error> {
     let tar_gz = File::open(source)?;
     let tar = BzDecoder::new(tar_gz);
-    let mut archive = Archive::new(tar);
-    archive.unpack(target)?;
-    Ok(())
+    unpack(Archive::new(tar), target, quiet)
 }
 
-fn fixup_sysconfig_paths(python_dir: &Path) -> Result<(), Error> {
-    let root = python_dir.join("python");
-    let lib = root
-        .join("lib")
-        .read_dir()?
-        .collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>()?
-        .into_iter()
-        .find(|dir| dir.file_name().to_str().unwrap().starts_with("python"))
-        .unwrap();
-    let sysconfig = lib
-        .path()
-        .read_dir()?
-        .collect::<Result<Vec<std::fs::DirEntry>, std::io::Error>>()?
-        .into_iter()
-        .find(|dir| {
-            dir.file_name()
-                .to_str()
-                .unwrap()
-                .contains("_sysconfigdata_")
-        })
-        .unwrap()
-        .path();
+fn extract_tar_xz(source: &Path, target: &Path, quiet: bool) -> Result<(), Error> {
+    let tar_xz = File::open(source)?;
+    let tar = XzDecoder::new(tar_xz);
+    unpack(Archive::new(tar), target, quiet)
+}
+
+/// Point the build paths of the interpreter in `staging` at where it will live in `python_dir`.
+fn fixup_sysconfig_paths(staging: &Path, python_dir: &Path) -> Result<(), Error> {
+    let root = staging.join("python");
+    let install_dir = python_dir.join("python");
+    // Windows builds, e.g. when downloading for another `--platform`, have no sysconfig data.
+    let Some(sysconfig) = find_sysconfig_data(&root)? else {
+        debug!("No sysconfig data to fix in {}", root.display());
+        return Ok(());
+    };
+    info!("Fixing paths in {}", sysconfig.display());
     let data = std::fs::read_to_string(&sysconfig)?;
-    let install_dir = root.to_str().unwrap();
+    let install_dir = install_dir
+        .to_str()
+        .ok_or_else(|| Error::NonUnicodePath(install_dir.clone()))?;
     let data = data.replace("'/install", &format!("'{}", install_dir));
     let data = data.replace(" /install", &format!(" {}", install_dir));
     let data = data.replace("=/install", &format!("={}", install_dir));
     std::fs::write(&sysconfig, data)?;
 
-    let pkgconfig = root.join("lib").join("pkgconfig");
-    for dir in pkgconfig.read_dir()? {
+    let pkgconfig = match root.join("lib").join("pkgconfig").read_dir() {
+        Ok(pkgconfig) => pkgconfig,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => Err(err)?,
+    };
+    for dir in pkgconfig {
         let path = dir?.path();
         if path.is_symlink() {
             continue;
         }
+        debug!("Fixing paths in {}", path.display());
         let data = std::fs::read_to_string(&path)?;
         let data = data.replace("=/install", &format!("={}", install_dir));
         std::fs::write(&path, data)?;
@@ -174,12 +884,572 @@ fn fixup_sysconfig_paths(python_dir: &Path) -> Result<(), Error> {
     Ok(())
 }
 
+/// Find `lib/pythonX.Y/_sysconfigdata_*.py` in an extracted interpreter, if it has one.
+fn find_sysconfig_data(root: &Path) -> Result<Option<PathBuf>, Error> {
+    match find_entry(&root.join("lib"), |name| name.starts_with("python"))? {
+        Some(lib) => find_entry(&lib, |name| name.contains("_sysconfigdata_")),
+        None => Ok(None),
+    }
+}
+
+/// The first entry of `dir` whose name matches, or `None` if there isn't one or no `dir`.
+fn find_entry(dir: &Path, matches: impl Fn(&str) -> bool) -> Result<Option<PathBuf>, Error> {
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => Err(err)?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        if matches(&entry.file_name().to_string_lossy()) {
+            return Ok(Some(entry.path()));
+        }
+    }
+    Ok(None)
+}
+
+/// `full` archives nest the interpreter under `python/install`, so move it up to `python`.
+/// `install_only` archives already have the interpreter in `python` and are left alone.
 fn move_install(python_dir: &Path) -> Result<(), std::io::Error> {
     let temp = python_dir.join("temp");
     let python_dir = python_dir.join("python");
     let install = python_dir.join("install");
+    if !install.is_dir() {
+        return Ok(());
+    }
+    debug!("Moving {} to {}", install.display(), python_dir.display());
     std::fs::rename(&install, &temp)?;
     std::fs::remove_dir_all(&python_dir)?;
     std::fs::rename(&temp, &python_dir)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::{parse_pypy_url, PYPY_DOWNLOAD_URL};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_preserves_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("cpython.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&source).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+
+        let contents = b"#!/bin/sh\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "python/bin/python3.12", &contents[..])
+            .unwrap();
+
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder
+            .append_link(&mut header, "python/bin/python3", "python3.12")
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let target = dir.path().join("extracted");
+        extract(&source, &target, true).unwrap();
+
+        let python = target.join("python/bin/python3.12");
+        let mode = python.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111);
+
+        let link = target.join("python/bin/python3");
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(
+            std::fs::read_link(&link).unwrap(),
+            PathBuf::from("python3.12")
+        );
+        assert!(link.is_file());
+    }
+
+    /// Write a gzipped tarball with a single file entry called `name`.
+    fn write_archive_with_entry(source: &Path, name: &[u8]) {
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(source).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+
+        // `Builder` refuses to write unsafe paths, so set the name by hand.
+        let contents = b"pwned";
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name);
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, &contents[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_rejects_path_traversal() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("evil.tar.gz");
+        write_archive_with_entry(&source, b"python/../../evil");
+
+        let target = dir.path().join("extracted");
+        let err = extract(&source, &target, true);
+        assert!(matches!(err, Err(Error::UnsafeArchivePath(_))));
+        assert!(!dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn test_extract_rejects_absolute_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("evil.tar.gz");
+        let evil = dir.path().join("evil");
+        write_archive_with_entry(&source, evil.to_str().unwrap().as_bytes());
+
+        let target = dir.path().join("extracted");
+        let err = extract(&source, &target, true);
+        assert!(matches!(err, Err(Error::UnsafeArchivePath(_))));
+        assert!(!evil.exists());
+        assert!(!target.join(evil.strip_prefix("/").unwrap()).exists());
+    }
+
+    #[test]
+    fn test_minor_versions() {
+        let releases = ["3.12.1", "3.11.9", "3.12.3t-debug", "3.13.0rc2", "3.11.8"]
+            .into_iter()
+            .map(|version| Python {
+                name: format!("cpython-{version}"),
+                url: Url::parse("https://example.com/python.tar.gz").unwrap(),
+                version: version.parse().unwrap(),
+                release_tag: "20240909".to_string(),
+            })
+            .collect::<Vec<_>>();
+        let versions = minor_versions(&releases)
+            .iter()
+            .map(Version::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(versions, ["3.11", "3.12", "3.13"]);
+    }
+
+    #[test]
+    fn test_latest_releases() {
+        let releases = [
+            ("3.12.1", "20240107"),
+            ("3.12.3", "20240415"),
+            ("3.12.3", "20240107"),
+            ("3.12.3-debug", "20240415"),
+            ("3.12.2", "20240909"),
+            ("3.13.0rc2", "20240909"),
+            ("3.13.0", "20241008"),
+            ("3.13.0rc3", "20241008"),
+            ("pypy3.10", "v7.3.9"),
+            ("pypy3.10", "v7.3.15"),
+        ]
+        .into_iter()
+        .map(|(version, release_tag)| Python {
+            name: format!("cpython-{version}"),
+            url: Url::parse("https://example.com/python.tar.gz").unwrap(),
+            version: version.parse().unwrap(),
+            release_tag: release_tag.to_string(),
+        })
+        .collect::<Vec<_>>();
+        let mut latest = latest_releases(releases)
+            .iter()
+            .map(|python| format!("{} ({})", python.version, python.release_tag))
+            .collect::<Vec<_>>();
+        latest.sort();
+        assert_eq!(
+            latest,
+            [
+                "3.12.3 (20240415)",
+                "3.12.3-debug (20240415)",
+                "3.13.0 (20241008)",
+                "pypy3.10 (v7.3.15)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matches_query() {
+        let python = |version: &str, release_tag: &str| Python {
+            name: String::new(),
+            url: Url::parse("https://example.com/python.tar.zst").unwrap(),
+            version: version.parse().unwrap(),
+            release_tag: release_tag.to_string(),
+        };
+        let query = |query: &str| query.split(' ').map(String::from).collect::<Vec<_>>();
+        let debug = python("3.12.1-debug", "20240107");
+        let freethreaded = python("3.13.0t", "20241008");
+        let pypy = python("pypy3.10", "v7.3.15");
+
+        assert!(matches_query(&debug, &[]));
+        assert!(matches_query(&debug, &query("3.12 debug")));
+        assert!(matches_query(&debug, &query("DEBUG cpython 202401")));
+        assert!(!matches_query(&debug, &query("3.12 freethreaded")));
+        assert!(matches_query(&freethreaded, &query("freethreaded 3.13")));
+        assert!(!matches_query(&freethreaded, &query("3.12")));
+        assert!(matches_query(&pypy, &query("pypy v7.3")));
+        assert!(!matches_query(&pypy, &query("cpython")));
+    }
+
+    #[test]
+    fn test_python_json() {
+        let python = Python {
+            name:
+                "cpython-3.13.0+20241008-x86_64-unknown-linux-gnu-freethreaded+debug-full.tar.zst"
+                    .to_string(),
+            url: Url::parse("https://example.com/cpython.tar.zst").unwrap(),
+            version: "3.13.0t-debug".parse().unwrap(),
+            release_tag: "20241008".to_string(),
+        };
+        assert_eq!(
+            python_json(&python),
+            serde_json::json!({
+                "interpreter": "cpython",
+                "version": "3.13.0t-debug",
+                "release_tag": "20241008",
+                "debug": true,
+                "freethreaded": true,
+                "url": "https://example.com/cpython.tar.zst",
+            })
+        );
+    }
+
+    #[test]
+    fn test_select_cpython_newest_bugfix() {
+        let releases = [
+            "cpython-3.12.1+20240107-x86_64-unknown-linux-gnu-install_only.tar.gz",
+            "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-install_only.tar.gz",
+            "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-pgo+lto-full.tar.zst",
+            "cpython-3.12.3+20240224-x86_64-unknown-linux-gnu-install_only.tar.gz",
+            "cpython-3.12.4+20240726-x86_64-unknown-linux-gnu-debug-full.tar.zst",
+            "cpython-3.13.0rc1+20240726-x86_64-unknown-linux-gnu-install_only.tar.gz",
+            "cpython-3.11.9+20240726-x86_64-unknown-linux-gnu-install_only.tar.gz",
+        ]
+        .into_iter()
+        .map(|name| {
+            let (release_tag, version) = crate::version::parse_cpython_filename(name).unwrap();
+            Python {
+                name: name.to_string(),
+                url: Url::parse("https://example.com/python.tar.gz").unwrap(),
+                version,
+                release_tag,
+            }
+        })
+        .collect::<Vec<_>>();
+
+        let python = select_cpython(releases, &"3.12".parse().unwrap(), false, false).unwrap();
+        assert_eq!(
+            python.name,
+            "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-install_only.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_select_cpython_archive_variant() {
+        let releases = || {
+            [
+                "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-pgo+lto-full.tar.zst",
+                "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-install_only_stripped.tar.gz",
+                "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-install_only.tar.gz",
+                "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-noopt-full.tar.zst",
+            ]
+            .into_iter()
+            .map(|name| {
+                let (release_tag, version) = crate::version::parse_cpython_filename(name).unwrap();
+                Python {
+                    name: name.to_string(),
+                    url: Url::parse("https://example.com/python.tar.gz").unwrap(),
+                    version,
+                    release_tag,
+                }
+            })
+            .collect::<Vec<_>>()
+        };
+        let version = "3.12".parse().unwrap();
+        let python = select_cpython(releases(), &version, false, false).unwrap();
+        assert_eq!(
+            python.name,
+            "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-install_only.tar.gz"
+        );
+        let python = select_cpython(releases(), &version, false, true).unwrap();
+        assert_eq!(
+            python.name,
+            "cpython-3.12.3+20240415-x86_64-unknown-linux-gnu-pgo+lto-full.tar.zst"
+        );
+    }
+
+    #[test]
+    fn test_select_cpython_prerelease() {
+        let python = |name: &str| {
+            let (release_tag, version) = crate::version::parse_cpython_filename(name).unwrap();
+            Python {
+                name: name.to_string(),
+                url: Url::parse("https://example.com/python.tar.gz").unwrap(),
+                version,
+                release_tag,
+            }
+        };
+        let releases = || {
+            vec![
+                python("cpython-3.14.0a6+20250317-x86_64-unknown-linux-gnu-install_only.tar.gz"),
+                python("cpython-3.14.0b1+20250517-x86_64-unknown-linux-gnu-install_only.tar.gz"),
+                python("cpython-3.14.0a7+20250409-x86_64-unknown-linux-gnu-install_only.tar.gz"),
+                python("cpython-3.13.3+20250517-x86_64-unknown-linux-gnu-install_only.tar.gz"),
+            ]
+        };
+        let version = "3.14".parse().unwrap();
+        assert!(select_cpython(releases(), &version, false, false).is_none());
+        let selected = select_cpython(releases(), &version, true, false).unwrap();
+        assert_eq!(selected.version.to_string(), "3.14.0b1");
+
+        let mut releases = releases();
+        releases.push(python(
+            "cpython-3.14.0+20251014-x86_64-unknown-linux-gnu-install_only.tar.gz",
+        ));
+        let selected = select_cpython(releases, &version, true, false).unwrap();
+        assert_eq!(selected.version.to_string(), "3.14.0");
+    }
+
+    #[test]
+    fn test_select_pypy() {
+        let releases = || {
+            [
+                "pypy3.10-v7.3.15-linux64.tar.bz2",
+                "pypy3.10.14-v7.3.17-linux64.tar.bz2",
+                "pypy3.9-v7.3.16-linux64.tar.bz2",
+            ]
+            .into_iter()
+            .map(|name| {
+                let url = format!("{PYPY_DOWNLOAD_URL}{name}");
+                let (name, release_tag, version) = parse_pypy_url(&url).unwrap();
+                Python {
+                    name,
+                    url: Url::parse(&url).unwrap(),
+                    version,
+                    release_tag,
+                }
+            })
+            .collect::<Vec<_>>()
+        };
+        let python = select_pypy(releases(), &"pypy3.10".parse().unwrap()).unwrap();
+        assert_eq!(python.version.to_string(), "pypy3.10.14");
+        assert_eq!(python.release_tag, "v7.3.17");
+        assert!(select_pypy(releases(), &"pypy3.11".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_select_cpython_build_variants() {
+        let releases = || {
+            [
+                "cpython-3.13.1+20241206-x86_64-unknown-linux-gnu-freethreaded+pgo+lto-full.tar.zst",
+                "cpython-3.13.1+20241206-x86_64-unknown-linux-gnu-debug-full.tar.zst",
+                "cpython-3.13.1+20241206-x86_64-unknown-linux-gnu-freethreaded+debug-full.tar.zst",
+                "cpython-3.13.0+20241008-x86_64-unknown-linux-gnu-install_only.tar.gz",
+            ]
+            .into_iter()
+            .map(|name| {
+                let (release_tag, version) =
+                    crate::version::parse_cpython_filename(name).unwrap();
+                Python {
+                    name: name.to_string(),
+                    url: Url::parse("https://example.com/python.tar.zst").unwrap(),
+                    version,
+                    release_tag,
+                }
+            })
+            .collect::<Vec<_>>()
+        };
+        for (requested, expected) in [
+            ("3.13", "3.13.0"),
+            ("3.13t", "3.13.1t"),
+            ("3.13-debug", "3.13.1-debug"),
+            ("3.13t-debug", "3.13.1t-debug"),
+        ] {
+            let version = requested.parse().unwrap();
+            for pre in [false, true] {
+                let python = select_cpython(releases(), &version, pre, false).unwrap();
+                assert_eq!(python.version.to_string(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress_writer() {
+        use std::io::Write;
+        let reports = std::cell::RefCell::new(vec![]);
+        let progress = |written, total| reports.borrow_mut().push((written, total));
+        let mut writer = ProgressWriter {
+            inner: vec![],
+            written: 10,
+            total: Some(16),
+            progress: &progress,
+        };
+        writer.write_all(b"abc").unwrap();
+        writer.write_all(b"def").unwrap();
+        assert_eq!(writer.inner, b"abcdef");
+        assert_eq!(*reports.borrow(), [(13, Some(16)), (16, Some(16))]);
+    }
+
+    #[test]
+    fn test_unsatisfied_range_size() {
+        assert_eq!(unsatisfied_range_size("bytes */31457280"), Some(31457280));
+        assert_eq!(unsatisfied_range_size("bytes 0-99/31457280"), None);
+        assert_eq!(unsatisfied_range_size("bytes */*"), None);
+    }
+
+    #[test]
+    fn test_part_file() {
+        let target = Path::new("/downloads/cpython-3.12.3+20240415-install_only.tar.gz");
+        assert_eq!(
+            part_file(target),
+            Path::new("/downloads/cpython-3.12.3+20240415-install_only.tar.gz.part")
+        );
+    }
+
+    #[test]
+    fn test_move_install_full_layout() {
+        let python_dir = tempfile::tempdir().unwrap();
+        let bin = python_dir.path().join("python/install/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("python3"), "").unwrap();
+        std::fs::create_dir_all(python_dir.path().join("python/build")).unwrap();
+
+        move_install(python_dir.path()).unwrap();
+
+        assert!(python_dir.path().join("python/bin/python3").is_file());
+        assert!(!python_dir.path().join("python/install").exists());
+        assert!(!python_dir.path().join("python/build").exists());
+        assert!(!python_dir.path().join("temp").exists());
+    }
+
+    #[test]
+    fn test_move_install_install_only_layout() {
+        let python_dir = tempfile::tempdir().unwrap();
+        let bin = python_dir.path().join("python/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("python3"), "").unwrap();
+
+        move_install(python_dir.path()).unwrap();
+
+        assert!(python_dir.path().join("python/bin/python3").is_file());
+    }
+
+    #[test]
+    fn test_is_downloaded() {
+        let python_dir = tempfile::tempdir().unwrap();
+        assert!(!is_downloaded(&python_dir.path().join("missing")));
+
+        let bin = python_dir.path().join("python/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(python_dir.path().join(COMPLETE_MARKER), "20240107").unwrap();
+        assert!(!is_downloaded(python_dir.path()));
+
+        std::fs::write(bin.join("python3"), "").unwrap();
+        assert!(is_downloaded(python_dir.path()));
+
+        std::fs::remove_file(python_dir.path().join(COMPLETE_MARKER)).unwrap();
+        assert!(!is_downloaded(python_dir.path()));
+    }
+
+    #[test]
+    fn test_install_python() {
+        let pythons = tempfile::tempdir().unwrap();
+        let python_dir = pythons.path().join("3.12");
+        let staging = staging_dir(&python_dir);
+        assert_eq!(staging, pythons.path().join("3.12.partial"));
+
+        let result = install_python(&python_dir, |staging| {
+            std::fs::create_dir_all(staging.join("python/bin"))?;
+            Err(Error::UnsupportedArchive("python.zip".to_string()))
+        });
+        assert!(matches!(result, Err(Error::UnsupportedArchive(_))));
+        assert!(!python_dir.exists());
+        assert!(!staging.exists());
+
+        install_python(&python_dir, |staging| {
+            std::fs::create_dir_all(staging.join("python/bin"))?;
+            std::fs::write(python_executable(staging), "")?;
+            write_complete_marker(staging, "20240107", &"3.12.1".parse().unwrap())
+        })
+        .unwrap();
+        assert!(is_downloaded(&python_dir));
+        assert!(python_dir.join("python/bin").is_dir());
+        assert!(!staging.exists());
+
+        // Only the interpreter is replaced, not anything else in the directory.
+        std::fs::write(python_dir.join("Dockerfile"), "").unwrap();
+        install_python(&python_dir, |staging| {
+            std::fs::create_dir_all(staging.join("python/bin"))?;
+            std::fs::create_dir_all(staging.join("python/lib"))?;
+            std::fs::write(python_executable(staging), "")?;
+            write_complete_marker(staging, "20240207", &"3.12.2".parse().unwrap())
+        })
+        .unwrap();
+        assert!(python_dir.join("Dockerfile").is_file());
+        assert!(python_dir.join("python/lib").is_dir());
+        assert_eq!(
+            downloaded_build(&python_dir).unwrap().release_tag,
+            "20240207"
+        );
+    }
+
+    #[test]
+    fn test_fixup_sysconfig_paths() {
+        let staging = tempfile::tempdir().unwrap();
+        let root = staging.path().join("python");
+        // A Windows layout has nothing to fix.
+        std::fs::create_dir_all(root.join("Lib/site-packages")).unwrap();
+        fixup_sysconfig_paths(staging.path(), Path::new("/pythons/3.12")).unwrap();
+
+        let lib = root.join("lib");
+        std::fs::create_dir_all(lib.join("python3.12")).unwrap();
+        std::fs::write(lib.join("libpython3.12.so"), "").unwrap();
+        let sysconfig = lib.join("python3.12/_sysconfigdata__linux_x86_64-linux-gnu.py");
+        std::fs::write(&sysconfig, "build_time_vars = {'prefix': '/install'}").unwrap();
+        fixup_sysconfig_paths(staging.path(), Path::new("/pythons/3.12")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&sysconfig).unwrap(),
+            "build_time_vars = {'prefix': '/pythons/3.12/python'}"
+        );
+    }
+
+    #[test]
+    fn test_is_extract_target() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(is_extract_target(&dir.path().join("missing")).unwrap());
+        assert!(is_extract_target(dir.path()).unwrap());
+
+        std::fs::write(dir.path().join("app.py"), "").unwrap();
+        assert!(!is_extract_target(dir.path()).unwrap());
+
+        std::fs::write(dir.path().join(COMPLETE_MARKER), "20240107").unwrap();
+        assert!(is_extract_target(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_complete_marker() {
+        assert_eq!(
+            parse_complete_marker("20240107\n3.12.1t-debug\n"),
+            Build {
+                release_tag: "20240107".to_string(),
+                version: Some("3.12.1t-debug".parse().unwrap()),
+            }
+        );
+        // Markers written before the version was recorded.
+        assert_eq!(
+            parse_complete_marker("20240107"),
+            Build {
+                release_tag: "20240107".to_string(),
+                version: None,
+            }
+        );
+    }
+}
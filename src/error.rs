@@ -1,7 +1,6 @@
 #[derive(Debug)]
 pub enum Error {
     Request(reqwest::Error),
-    Octocrab(octocrab::Error),
     Scraper(String),
     Url(url::ParseError),
     Fs(std::io::Error),
@@ -10,13 +9,134 @@ pub enum Error {
     ParseAsset(String),
     Platform(String),
     EnvVar(std::env::VarError),
+    InvalidShell(String, String),
+    NotActivated,
+    Timeout(String),
+    InvalidTimeout(String, String),
+    Proxy(String, reqwest::Error),
+    InvalidRepository(String),
+    CreateFile(std::path::PathBuf, std::io::Error),
+    UnsupportedArchive(String),
+    MissingSitePackages(std::path::PathBuf),
+    CommandFailed(String),
+    NotFrozen(String),
+    DownloadsFailed(String),
+    NoVirtualenvs(String),
+    AmbiguousVersion(String, String),
+    UnsafeArchivePath(String),
+    Offline(String),
+    ForeignPlatform(String),
+    NoHomeDirectory,
+    MissingUv(std::path::PathBuf),
+    ShellNotFound(String),
+    MissingInterpreter(String, std::path::PathBuf),
+    Unreachable(String, reqwest::Error),
+    InvalidCutoff(String),
+    InvalidAlias(String),
+    AliasNotFound(String),
+    ExtractTarget(std::path::PathBuf),
+    UpgradeBugfix(String),
+    Logger(log::SetLoggerError),
+    PlatformAlreadySet(String),
+    NonUnicodePath(std::path::PathBuf),
+}
+
+impl Error {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Request(_) => "Request",
+            Self::Scraper(_) => "Scraper",
+            Self::Url(_) => "Url",
+            Self::Fs(_) => "Fs",
+            Self::VersionNotFound(_) => "VersionNotFound",
+            Self::InvalidVersion(_) => "InvalidVersion",
+            Self::ParseAsset(_) => "ParseAsset",
+            Self::Platform(_) => "Platform",
+            Self::EnvVar(_) => "EnvVar",
+            Self::InvalidShell(_, _) => "InvalidShell",
+            Self::NotActivated => "NotActivated",
+            Self::Timeout(_) => "Timeout",
+            Self::InvalidTimeout(_, _) => "InvalidTimeout",
+            Self::Proxy(_, _) => "Proxy",
+            Self::InvalidRepository(_) => "InvalidRepository",
+            Self::CreateFile(_, _) => "CreateFile",
+            Self::UnsupportedArchive(_) => "UnsupportedArchive",
+            Self::MissingSitePackages(_) => "MissingSitePackages",
+            Self::CommandFailed(_) => "CommandFailed",
+            Self::NotFrozen(_) => "NotFrozen",
+            Self::DownloadsFailed(_) => "DownloadsFailed",
+            Self::NoVirtualenvs(_) => "NoVirtualenvs",
+            Self::AmbiguousVersion(_, _) => "AmbiguousVersion",
+            Self::UnsafeArchivePath(_) => "UnsafeArchivePath",
+            Self::Offline(_) => "Offline",
+            Self::ForeignPlatform(_) => "ForeignPlatform",
+            Self::NoHomeDirectory => "NoHomeDirectory",
+            Self::MissingUv(_) => "MissingUv",
+            Self::ShellNotFound(_) => "ShellNotFound",
+            Self::MissingInterpreter(_, _) => "MissingInterpreter",
+            Self::Unreachable(_, _) => "Unreachable",
+            Self::InvalidCutoff(_) => "InvalidCutoff",
+            Self::InvalidAlias(_) => "InvalidAlias",
+            Self::AliasNotFound(_) => "AliasNotFound",
+            Self::ExtractTarget(_) => "ExtractTarget",
+            Self::UpgradeBugfix(_) => "UpgradeBugfix",
+            Self::Logger(_) => "Logger",
+            Self::PlatformAlreadySet(_) => "PlatformAlreadySet",
+            Self::NonUnicodePath(_) => "NonUnicodePath",
+        }
+    }
+}
+
+/// Exit code for network failures.
+pub const EXIT_NETWORK: i32 = 3;
+/// Exit code when a version, virtualenv or other thing asked for doesn't exist.
+pub const EXIT_NOT_FOUND: i32 = 4;
+/// Exit code for invalid versions and other invalid input.
+pub const EXIT_INVALID: i32 = 5;
+/// Exit code for filesystem failures.
+pub const EXIT_FILESYSTEM: i32 = 6;
+
+impl Error {
+    /// The process exit code for this error, so scripts can tell kinds of failure apart.
+    /// Errors outside the categories above exit with 1.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Request(_)
+            | Self::Scraper(_)
+            | Self::Timeout(_)
+            | Self::Proxy(_, _)
+            | Self::Offline(_)
+            | Self::Unreachable(_, _) => EXIT_NETWORK,
+            Self::VersionNotFound(_)
+            | Self::NoVirtualenvs(_)
+            | Self::NotFrozen(_)
+            | Self::NotActivated
+            | Self::MissingSitePackages(_)
+            | Self::MissingInterpreter(_, _)
+            | Self::MissingUv(_)
+            | Self::ShellNotFound(_)
+            | Self::AliasNotFound(_) => EXIT_NOT_FOUND,
+            Self::InvalidVersion(_)
+            | Self::AmbiguousVersion(_, _)
+            | Self::InvalidShell(_, _)
+            | Self::InvalidTimeout(_, _)
+            | Self::InvalidRepository(_)
+            | Self::ForeignPlatform(_)
+            | Self::InvalidCutoff(_)
+            | Self::InvalidAlias(_)
+            | Self::ExtractTarget(_)
+            | Self::UpgradeBugfix(_)
+            | Self::NonUnicodePath(_) => EXIT_INVALID,
+            Self::Fs(_) | Self::CreateFile(_, _) | Self::NoHomeDirectory => EXIT_FILESYSTEM,
+            _ => 1,
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Request(err) => write!(f, "{err}"),
-            Self::Octocrab(err) => write!(f, "{err}"),
             Self::Fs(err) => write!(f, "{err}"),
             Self::Url(err) => write!(f, "{err}"),
             Self::VersionNotFound(version) => write!(f, "Could not find {version} to download."),
@@ -27,6 +147,99 @@ impl std::fmt::Display for Error {
             Self::Scraper(error) => write!(f, "{error}"),
             Self::Platform(platform) => write!(f, "{platform} is not supported."),
             Self::EnvVar(error) => write!(f, "{error}"),
+            Self::InvalidShell(shell, supported) => {
+                write!(
+                    f,
+                    "{shell} is not a supported shell. Choose one of: {supported}."
+                )
+            }
+            Self::NotActivated => write!(f, "No virtualenv was activated with `lilyenv env`."),
+            Self::Timeout(url) => write!(f, "The request to {url} timed out."),
+            Self::InvalidTimeout(name, timeout) => {
+                write!(f, "{name} must be a number of seconds, not {timeout}.")
+            }
+            Self::Proxy(proxy, err) => write!(f, "Could not connect via proxy {proxy}: {err}"),
+            Self::InvalidRepository(repo) => {
+                write!(
+                    f,
+                    "{repo} is not a GitHub repository in the form owner/name."
+                )
+            }
+            Self::CreateFile(path, err) => write!(f, "Could not create {}: {err}", path.display()),
+            Self::UnsupportedArchive(name) => {
+                write!(f, "Could not extract {name}: unsupported archive format.")
+            }
+            Self::MissingSitePackages(lib) => {
+                write!(f, "Could not find site-packages in {}.", lib.display())
+            }
+            Self::CommandFailed(command) => write!(f, "`{command}` failed."),
+            Self::NotFrozen(virtualenv) => {
+                write!(f, "No packages have been frozen for {virtualenv}.")
+            }
+            Self::DownloadsFailed(versions) => write!(f, "Could not download {versions}."),
+            Self::NoVirtualenvs(project) => write!(f, "{project} has no virtualenvs."),
+            Self::AmbiguousVersion(project, versions) => write!(
+                f,
+                "{project} has several virtualenvs, choose a version from: {versions}"
+            ),
+            Self::UnsafeArchivePath(path) => write!(
+                f,
+                "Refusing to extract {path} because it is outside the target directory."
+            ),
+            Self::Offline(action) => write!(f, "Could not {action} in offline mode."),
+            Self::ForeignPlatform(platform) => write!(
+                f,
+                "Interpreters for {platform} can be downloaded but not used to create virtualenvs here."
+            ),
+            Self::NoHomeDirectory => write!(
+                f,
+                "Could not find your home directory to store lilyenv's files in. Check that $HOME is set."
+            ),
+            Self::MissingUv(lock) => write!(
+                f,
+                "Installing {} needs uv, which is not on your PATH. Install uv from https://docs.astral.sh/uv/ or use `uv export` to write a requirements file instead.",
+                lock.display()
+            ),
+            Self::ShellNotFound(shell) => write!(
+                f,
+                "Could not find {shell} on your PATH. Pass --force to use it anyway."
+            ),
+            Self::MissingInterpreter(version, path) => write!(
+                f,
+                "Could not find Python {version} at {}. Run `lilyenv download --force {version}` to download it again.",
+                path.display()
+            ),
+            Self::Unreachable(host, err) => write!(
+                f,
+                "Could not reach {host} (are you offline?): {err}. Pass --offline to use only downloaded interpreters."
+            ),
+            Self::InvalidCutoff(date) => write!(
+                f,
+                "LILYENV_CPYTHON_CUTOFF must be a date like 2022-02-26, not {date}."
+            ),
+            Self::InvalidAlias(alias) => write!(
+                f,
+                "{alias:?} can't be used as an alias: it must not be empty or contain `=` or whitespace."
+            ),
+            Self::AliasNotFound(alias) => write!(f, "There is no alias called {alias}."),
+            Self::NonUnicodePath(path) => write!(
+                f,
+                "{} can't be written into the interpreter's configuration because it isn't valid utf-8.",
+                path.display()
+            ),
+            Self::Logger(err) => write!(f, "Could not set up logging: {err}."),
+            Self::PlatformAlreadySet(platform) => {
+                write!(f, "The platform has already been set to {platform}.")
+            }
+            Self::UpgradeBugfix(version) => write!(
+                f,
+                "Only x.y Python versions can be upgraded, not x.y.z like {version}."
+            ),
+            Self::ExtractTarget(path) => write!(
+                f,
+                "Not extracting to {}: it isn't empty and doesn't hold an interpreter lilyenv installed.",
+                path.display()
+            ),
         }
     }
 }
@@ -35,7 +248,10 @@ impl std::error::Error for Error {}
 
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Self {
-        Self::Request(err)
+        match (err.is_timeout(), err.url()) {
+            (true, Some(url)) => Self::Timeout(url.to_string()),
+            _ => Self::Request(err),
+        }
     }
 }
 
@@ -45,9 +261,9 @@ impl From<std::io::Error> for Error {
     }
 }
 
-impl From<octocrab::Error> for Error {
-    fn from(err: octocrab::Error) -> Self {
-        Self::Octocrab(err)
+impl From<log::SetLoggerError> for Error {
+    fn from(err: log::SetLoggerError) -> Self {
+        Self::Logger(err)
     }
 }
 
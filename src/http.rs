@@ -0,0 +1,120 @@
+use crate::error::Error;
+use log::debug;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::{NoProxy, Proxy};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const USER_AGENT: &str = concat!("lilyenv/", env!("CARGO_PKG_VERSION"));
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Listing releases is the first thing to fail without a network, so give up on it quickly.
+const METADATA_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const METADATA_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Make every later attempt to build a client fail with `Error::Offline`.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Read a timeout in seconds from the environment variable `name`.
+fn timeout(name: &str, default: Duration) -> Result<Duration, Error> {
+    match std::env::var(name) {
+        Ok(seconds) => match seconds.parse() {
+            Ok(seconds) => Ok(Duration::from_secs(seconds)),
+            Err(_) => Err(Error::InvalidTimeout(name.to_string(), seconds)),
+        },
+        Err(std::env::VarError::NotPresent) => Ok(default),
+        Err(err) => Err(err)?,
+    }
+}
+
+/// The user agent to send, from `LILYENV_USER_AGENT` if it is set.
+fn user_agent() -> String {
+    std::env::var("LILYENV_USER_AGENT")
+        .ok()
+        .filter(|agent| !agent.is_empty())
+        .unwrap_or_else(|| USER_AGENT.to_string())
+}
+
+/// Read a proxy url from the upper or lower case form of an environment variable.
+fn proxy_var(name: &str) -> Option<String> {
+    std::env::var(name.to_uppercase())
+        .or_else(|_| std::env::var(name.to_lowercase()))
+        .ok()
+        .filter(|proxy| !proxy.is_empty())
+}
+
+fn proxy_for(scheme: &str) -> Option<String> {
+    match scheme {
+        "https" => proxy_var("https_proxy").or_else(|| proxy_var("all_proxy")),
+        "http" => proxy_var("http_proxy").or_else(|| proxy_var("all_proxy")),
+        _ => None,
+    }
+}
+
+/// Configure proxies from `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY`.
+fn with_proxies(mut builder: ClientBuilder) -> Result<ClientBuilder, Error> {
+    builder = builder.no_proxy();
+    if let Some(proxy) = proxy_for("https") {
+        builder = builder.proxy(Proxy::https(proxy)?.no_proxy(NoProxy::from_env()));
+    }
+    if let Some(proxy) = proxy_for("http") {
+        builder = builder.proxy(Proxy::http(proxy)?.no_proxy(NoProxy::from_env()));
+    }
+    Ok(builder)
+}
+
+/// A client for small requests, like listing releases, which should fail fast.
+pub fn metadata_client() -> Result<Client, Error> {
+    if is_offline() {
+        return Err(Error::Offline(
+            "fetch the list of Python releases".to_string(),
+        ));
+    }
+    Ok(with_proxies(Client::builder())?
+        .user_agent(user_agent())
+        .connect_timeout(METADATA_CONNECT_TIMEOUT)
+        .timeout(timeout("LILYENV_TIMEOUT", METADATA_TIMEOUT)?)
+        .build()?)
+}
+
+/// A client for downloading interpreters, which may take several minutes to transfer.
+pub fn download_client() -> Result<Client, Error> {
+    if is_offline() {
+        return Err(Error::Offline("download a Python interpreter".to_string()));
+    }
+    Ok(with_proxies(Client::builder())?
+        .user_agent(user_agent())
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(timeout("LILYENV_DOWNLOAD_TIMEOUT", DOWNLOAD_TIMEOUT)?)
+        .build()?)
+}
+
+/// Send a request, reporting connection failures against the proxy if one is configured, or
+/// as the host being unreachable otherwise.
+pub fn send(request: RequestBuilder) -> Result<Response, Error> {
+    match request.send() {
+        Ok(response) => {
+            debug!("{} {}", response.status(), response.url());
+            Ok(response)
+        }
+        Err(err) if err.is_connect() => {
+            let url = err.url().cloned();
+            match url.as_ref().and_then(|url| proxy_for(url.scheme())) {
+                Some(proxy) => Err(Error::Proxy(proxy, err)),
+                None => match url.as_ref().and_then(|url| url.host_str()) {
+                    Some(host) => Err(Error::Unreachable(host.to_string(), err)),
+                    None => Err(err)?,
+                },
+            }
+        }
+        Err(err) => Err(err)?,
+    }
+}
@@ -0,0 +1,26 @@
+//! Lilyenv manages Python interpreters and virtualenvs.
+//!
+//! The `lilyenv` binary is a thin command line interface over this library. The most
+//! useful parts are re-exported here, and the modules hold the rest.
+
+pub mod directories;
+pub mod download;
+pub mod error;
+pub mod http;
+pub mod logging;
+pub mod releases;
+pub mod shell;
+pub mod version;
+pub mod virtualenvs;
+
+pub use crate::download::{
+    download_python, download_python_with_progress, downloaded_build, extract_python,
+    is_downloaded, Build, DownloadOptions, Progress,
+};
+pub use crate::error::Error;
+pub use crate::releases::{cpython_releases, pypy_releases, Python};
+pub use crate::version::{Interpreter, PreRelease, Version};
+pub use crate::virtualenvs::{
+    create_virtualenv, freeze_packages, reinstall_virtualenv, remove_project, remove_virtualenv,
+    repair_virtualenv, resolve_version, restore_packages, run_pip, Backend, VirtualenvOptions,
+};
@@ -0,0 +1,41 @@
+use crate::error::Error;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes lilyenv's log messages to stderr. Other crates' messages are only shown at the
+/// most verbose level.
+struct StderrLogger {
+    level: LevelFilter,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+            && (self.level == LevelFilter::Trace || metadata.target().starts_with("lilyenv"))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            match record.level() {
+                Level::Error | Level::Warn | Level::Info => eprintln!("{}", record.args()),
+                level => eprintln!("[{} {}] {}", level, record.target(), record.args()),
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Send log messages to stderr, showing more of them the higher `verbosity` is.
+///
+/// Fails if a logger has already been set, by lilyenv or the program embedding it.
+pub fn init(verbosity: u8) -> Result<(), Error> {
+    let level = match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_logger(Box::leak(Box::new(StderrLogger { level })))?;
+    log::set_max_level(level);
+    Ok(())
+}
@@ -1,25 +1,53 @@
-use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
 
-mod directories;
-mod download;
-mod error;
-mod releases;
-mod shell;
-mod version;
-mod virtualenvs;
-use crate::download::{download_python, print_available_downloads};
-use crate::error::Error;
-use crate::shell::{print_shell_config, set_shell};
-use crate::version::Version;
-use crate::virtualenvs::{
-    activate_virtualenv, cd_site_packages, create_virtualenv, print_all_versions,
-    print_project_versions, remove_project, remove_virtualenv, set_project_directory,
-    unset_project_directory,
+use lilyenv::download::{
+    download_python, download_pythons, extract_python, latest_bugfix, print_available_downloads,
+    print_available_downloads_json, print_available_versions, DownloadOptions,
 };
+use lilyenv::error::Error;
+use lilyenv::shell::{get_shell, print_shell_config, set_shell};
+use lilyenv::version::{Interpreter, Version};
+use lilyenv::virtualenvs::{
+    activate_scratch_virtualenv, activate_virtualenv, cd_site_packages, create_virtualenv,
+    freeze_packages, install_lockfile, print_activation_env, print_activation_script,
+    print_aliases, print_all_versions, print_deactivation_env, print_dependents,
+    print_project_versions, print_prompt, reinstall_virtualenv, remove_project, remove_virtualenv,
+    repair_all_virtualenvs, repair_virtualenv, resolve_alias, resolve_version, restore_packages,
+    run_pip, set_alias, set_hook, set_project_directory, unset_alias, unset_hook,
+    unset_project_directory, Backend, VirtualenvOptions, DEFAULT_PROMPT,
+};
+use lilyenv::{directories, http, logging, releases};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
 struct Cli {
+    /// Print errors as JSON objects on stderr
+    #[arg(
+        long,
+        global = true,
+        env = "LILYENV_JSON",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    json: bool,
+    /// Only use interpreters that have already been downloaded
+    #[arg(
+        long,
+        global = true,
+        env = "LILYENV_OFFLINE",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    offline: bool,
+    /// Fetch the lists of available interpreters again instead of using cached ones
+    #[arg(long, global = true)]
+    refresh: bool,
+    /// Download interpreters for this target triple instead of the current platform
+    #[arg(long, global = true, env = "LILYENV_PLATFORM")]
+    platform: Option<String>,
+    /// Show what lilyenv is doing; repeat for more detail
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
     #[command(subcommand)]
     cmd: Commands,
 }
@@ -27,13 +55,98 @@ struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
     /// Activate a virtualenv given a Project string and a Python version
-    Activate { project: String, version: Version },
+    Activate {
+        project: String,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+        /// Start the shell in this directory instead of the project's default directory
+        #[arg(long)]
+        directory: Option<String>,
+        /// Save the directory as the project's default directory
+        #[arg(long, requires = "directory")]
+        save_directory: bool,
+        /// Use this shell instead of the configured one
+        #[arg(long)]
+        shell: Option<String>,
+        /// The prompt label, with `{project}` and `{version}` placeholders
+        #[arg(long, env = "LILYENV_PROMPT", default_value = DEFAULT_PROMPT)]
+        prompt: String,
+    },
+    /// Activate a throwaway virtualenv that is removed when the shell exits
+    Scratch {
+        version: Version,
+        /// Use this shell instead of the configured one
+        #[arg(long)]
+        shell: Option<String>,
+        /// The prompt label, with `{project}` and `{version}` placeholders
+        #[arg(long, env = "LILYENV_PROMPT", default_value = DEFAULT_PROMPT)]
+        prompt: String,
+    },
+    /// Print shell commands that activate a virtualenv, for use with `eval`
+    Env {
+        project: String,
+        version: Version,
+        /// The prompt label, with `{project}` and `{version}` placeholders
+        #[arg(long, env = "LILYENV_PROMPT", default_value = DEFAULT_PROMPT)]
+        prompt: String,
+    },
+    /// Print a standalone shell script that activates a virtualenv when sourced
+    GenerateScript {
+        project: String,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+        /// The prompt label, with `{project}` and `{version}` placeholders
+        #[arg(long, env = "LILYENV_PROMPT", default_value = DEFAULT_PROMPT)]
+        prompt: String,
+    },
+    /// Print the prompt label of the active virtualenv, for use in a shell prompt
+    Prompt {
+        /// The prompt label, with `{project}` and `{version}` placeholders
+        #[arg(long, env = "LILYENV_PROMPT", default_value = DEFAULT_PROMPT)]
+        prompt: String,
+    },
+    /// Print shell commands that undo `env`, for use with `eval`
+    Deactivate,
     /// List all available virtualenvs, or those for the given Project
-    List { project: Option<String> },
+    List {
+        project: Option<String>,
+        /// Also show each virtualenv's interpreter, creation date and package count
+        #[arg(short, long)]
+        long: bool,
+        /// Also show each project's default directory
+        #[arg(long)]
+        project_paths: bool,
+    },
+    /// List the virtualenvs created with a downloaded Python version
+    Dependents { version: Version },
     /// Upgrade a Python version to the latest bugfix release
-    Upgrade { version: Version },
+    Upgrade {
+        version: Version,
+        /// Upgrade to alpha, beta and release candidate builds too
+        #[arg(long)]
+        pre: bool,
+    },
+    /// Run a virtualenv's pip with the arguments given after `--`
+    Pip {
+        project: String,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Save the packages installed in a virtualenv
+    Freeze { project: String, version: Version },
+    /// Install the packages saved by `freeze` into a virtualenv
+    Restore { project: String, version: Version },
     /// Open a subshell in a virtualenv's site packages
-    SitePackages { project: String, version: Version },
+    SitePackages {
+        project: String,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+        /// Use this shell instead of the configured one
+        #[arg(long)]
+        shell: Option<String>,
+    },
     /// Set the default directory for a project
     SetProjectDirectory {
         project: String,
@@ -41,51 +154,344 @@ enum Commands {
     },
     /// Unset the default directory for a project
     UnsetProjectDirectory { project: String },
+    /// Set a script for the shell to source when a project is activated
+    SetHook { project: String, script: PathBuf },
+    /// Remove the script sourced when a project is activated
+    UnsetHook { project: String },
+    /// Save a short name to use instead of a project's name
+    SetAlias { alias: String, project: String },
+    /// Remove a project alias
+    UnsetAlias { alias: String },
+    /// List the project aliases
+    Aliases,
     /// Create a virtualenv given a Project string and a Python version
-    Virtualenv { project: String, version: Version },
+    Virtualenv {
+        project: String,
+        #[arg(env = "LILYENV_PYTHON")]
+        version: Version,
+        /// Create the virtualenv with this Python interpreter instead of downloading one
+        #[arg(long)]
+        python_path: Option<PathBuf>,
+        /// Remove any existing virtualenv first and create a clean one
+        #[arg(long)]
+        force: bool,
+        /// Install the packages pinned in a lockfile, like `uv.lock` or a hashed requirements file
+        #[arg(long)]
+        lock: Option<PathBuf>,
+        /// Use `latest` to create the virtualenv for the newest bugfix release, named after it
+        #[arg(long, value_parser = ["latest"])]
+        bugfix: Option<String>,
+        /// Pass an extra argument to the backend, like `--venv-arg=--copies`; can be repeated
+        #[arg(long, allow_hyphen_values = true)]
+        venv_arg: Vec<String>,
+        /// The tool to create the virtualenv with
+        #[arg(
+            long,
+            env = "LILYENV_BACKEND",
+            value_parser = ["venv", "virtualenv", "uv"],
+            default_value = "venv"
+        )]
+        backend: String,
+    },
+    /// Recreate a virtualenv with the same interpreter and packages
+    Reinstall {
+        project: String,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+    },
+    /// Point a virtualenv at lilyenv's interpreter again after it has moved
+    Repair {
+        #[arg(required_unless_present = "all")]
+        project: Option<String>,
+        /// Can be omitted if the project has only one virtualenv
+        version: Option<Version>,
+        /// Repair every virtualenv
+        #[arg(long, conflicts_with_all = ["project", "version"])]
+        all: bool,
+    },
     /// Remove a virtualenv
     RemoveVirtualenv { project: String, version: Version },
     /// Remove all virtualenvs for a project
     RemoveProject { project: String },
-    /// Download a specific Python version or list all Python versions available to download
-    Download { version: Option<Version> },
+    /// Download specific Python versions or list all Python versions available to download
+    Download {
+        versions: Vec<Version>,
+        /// Continue downloading the remaining versions when one fails
+        #[arg(long)]
+        keep_going: bool,
+        /// How many versions to download at once
+        #[arg(long, short, default_value_t = 2, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: u16,
+        /// Remove an existing download of this version and download it again
+        #[arg(long)]
+        force: bool,
+        /// Don't show which file is being downloaded
+        #[arg(long, short)]
+        quiet: bool,
+        /// Print the directory each interpreter is installed in
+        #[arg(long)]
+        print_path: bool,
+        /// Download the `full` archive, with build artifacts and tests, instead of `install_only`
+        #[arg(long)]
+        full: bool,
+        /// Install the interpreter in this directory instead of lilyenv's own
+        #[arg(long, value_name = "PATH")]
+        extract_to: Option<PathBuf>,
+        /// How to list the available downloads when no versions are given
+        #[arg(long, value_parser = ["human", "json"], default_value = "human")]
+        format: String,
+        /// Only list the newest release of each Python version and build variant
+        #[arg(long)]
+        latest_only: bool,
+    },
+    /// List the Python versions available to download that match every search term
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        /// Only list the newest release of each Python version and build variant
+        #[arg(long)]
+        latest_only: bool,
+    },
+    /// List the major.minor Python versions available to download
+    Versions {
+        #[arg(value_parser = ["cpython", "pypy"])]
+        interpreter: Option<String>,
+    },
     /// Explicitly set the shell for lilyenv to use
-    SetShell { shell: String },
+    SetShell {
+        shell: String,
+        /// Save the shell even if it isn't supported or can't be found on PATH
+        #[arg(long)]
+        force: bool,
+    },
     /// Show information to include in a shell config file
     ShellConfig,
+    /// Show lilyenv's version, platform, directories and shell, e.g. for bug reports
+    Info,
 }
 
-fn run() -> Result<(), Error> {
-    let cli = Cli::parse();
+fn print_info() -> Result<(), Error> {
+    println!("lilyenv {}", env!("CARGO_PKG_VERSION"));
+    println!("Platform: {}", current_platform::CURRENT_PLATFORM);
+    if releases::is_foreign_platform() {
+        println!("Downloading for: {}", releases::platform());
+    }
+    println!("Downloads: {}", directories::downloads_dir()?.display());
+    println!("Pythons: {}", directories::pythons_dir()?.display());
+    println!("Virtualenvs: {}", directories::virtualenvs_dir()?.display());
+    match get_shell() {
+        Ok(shell) => println!("Shell: {shell}"),
+        Err(err) => println!("Shell: unknown ({err})"),
+    }
+    println!("Offline: {}", http::is_offline());
+    // Release listings are fetched anonymously, so a token only matters to other tools.
+    let tokens = ["GITHUB_TOKEN", "GH_TOKEN"]
+        .into_iter()
+        .filter(|name| std::env::var_os(name).is_some_and(|token| !token.is_empty()))
+        .collect::<Vec<_>>();
+    match tokens.is_empty() {
+        true => println!("GitHub token: not set"),
+        false => println!(
+            "GitHub token: {} set (ignored by lilyenv)",
+            tokens.join(", ")
+        ),
+    }
+    Ok(())
+}
 
-    match cli.cmd {
-        Commands::Download { version: None } => print_available_downloads()?,
+impl Commands {
+    /// The project this command acts on, if it takes one.
+    fn project_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Commands::Activate { project, .. }
+            | Commands::Env { project, .. }
+            | Commands::GenerateScript { project, .. }
+            | Commands::Pip { project, .. }
+            | Commands::Freeze { project, .. }
+            | Commands::Restore { project, .. }
+            | Commands::SitePackages { project, .. }
+            | Commands::SetProjectDirectory { project, .. }
+            | Commands::UnsetProjectDirectory { project }
+            | Commands::SetHook { project, .. }
+            | Commands::UnsetHook { project }
+            | Commands::Virtualenv { project, .. }
+            | Commands::Reinstall { project, .. }
+            | Commands::RemoveVirtualenv { project, .. }
+            | Commands::RemoveProject { project } => Some(project),
+            Commands::List { project, .. } | Commands::Repair { project, .. } => project.as_mut(),
+            _ => None,
+        }
+    }
+}
+
+fn run(mut cmd: Commands) -> Result<(), Error> {
+    if let Some(project) = cmd.project_mut() {
+        *project = resolve_alias(project)?;
+    }
+    match cmd {
         Commands::Download {
-            version: Some(version),
+            versions,
+            keep_going,
+            jobs,
+            force,
+            quiet,
+            print_path,
+            full,
+            extract_to,
+            format,
+            latest_only,
+        } => match versions.is_empty() {
+            true => match format.as_str() {
+                "json" => print_available_downloads_json()?,
+                _ => print_available_downloads(&[], latest_only)?,
+            },
+            false => {
+                let options = DownloadOptions {
+                    force,
+                    quiet,
+                    full,
+                    ..Default::default()
+                };
+                let paths = match extract_to {
+                    Some(target) => {
+                        if versions.len() > 1 {
+                            Cli::command()
+                                .error(
+                                    ErrorKind::TooManyValues,
+                                    "--extract-to takes a single version",
+                                )
+                                .exit();
+                        }
+                        vec![extract_python(&versions[0], options, &target)?]
+                    }
+                    None => download_pythons(&versions, options, keep_going, jobs.into())?,
+                };
+                if print_path {
+                    for path in paths {
+                        println!("{}", path.display());
+                    }
+                }
+            }
+        },
+        Commands::Search { query, latest_only } => print_available_downloads(&query, latest_only)?,
+        Commands::Versions { interpreter } => {
+            let interpreter = match interpreter.as_deref() {
+                Some("cpython") => Some(Interpreter::CPython),
+                Some("pypy") => Some(Interpreter::PyPy),
+                _ => None,
+            };
+            print_available_versions(interpreter)?;
+        }
+        Commands::Virtualenv {
+            version,
+            project,
+            python_path,
+            force,
+            lock,
+            bugfix,
+            venv_arg,
+            backend,
         } => {
-            download_python(&version, false)?;
+            let version = match bugfix {
+                Some(_) => latest_bugfix(&version)?,
+                None => version,
+            };
+            let options = VirtualenvOptions {
+                python_path,
+                force,
+                venv_args: venv_arg,
+                backend: Backend::from_name(&backend).unwrap_or_default(),
+            };
+            create_virtualenv(&version, &project, &options)?;
+            if let Some(lock) = lock {
+                install_lockfile(&version, &project, &lock)?;
+            }
         }
-        Commands::Virtualenv { version, project } => {
-            create_virtualenv(&version, &project)?;
+        Commands::Reinstall { project, version } => {
+            let version = resolve_version(&project, version)?;
+            reinstall_virtualenv(&version, &project)?;
         }
+        Commands::Repair {
+            project,
+            version,
+            all,
+        } => match project {
+            Some(project) if !all => {
+                let version = resolve_version(&project, version)?;
+                repair_virtualenv(&project, &version)?;
+            }
+            _ => repair_all_virtualenvs()?,
+        },
         Commands::RemoveVirtualenv { project, version } => {
             remove_virtualenv(&project, &version)?;
         }
         Commands::RemoveProject { project } => {
             remove_project(&project)?;
         }
-        Commands::Activate { version, project } => {
-            activate_virtualenv(&version, &project)?;
+        Commands::Activate {
+            version,
+            project,
+            directory,
+            save_directory,
+            shell,
+            prompt,
+        } => {
+            let version = resolve_version(&project, version)?;
+            activate_virtualenv(
+                &version,
+                &project,
+                directory.as_deref(),
+                save_directory,
+                shell.as_deref(),
+                &prompt,
+            )?;
         }
-        Commands::SetShell { shell } => set_shell(&shell)?,
+        Commands::Scratch {
+            version,
+            shell,
+            prompt,
+        } => activate_scratch_virtualenv(&version, shell.as_deref(), &prompt)?,
+        Commands::Env {
+            project,
+            version,
+            prompt,
+        } => {
+            print_activation_env(&version, &project, &prompt)?;
+        }
+        Commands::GenerateScript {
+            project,
+            version,
+            prompt,
+        } => {
+            let version = resolve_version(&project, version)?;
+            print_activation_script(&version, &project, &prompt)?;
+        }
+        Commands::Prompt { prompt } => print_prompt(&prompt)?,
+        Commands::Deactivate => print_deactivation_env()?,
+        Commands::SetShell { shell, force } => set_shell(&shell, force)?,
         Commands::ShellConfig => print_shell_config()?,
-        Commands::List { project } => match project {
-            Some(project) => print_project_versions(project)?,
-            None => print_all_versions()?,
+        Commands::Info => print_info()?,
+        Commands::List {
+            project,
+            long,
+            project_paths,
+        } => match project {
+            Some(project) => print_project_versions(project, long, project_paths)?,
+            None => print_all_versions(long, project_paths)?,
         },
-        Commands::Upgrade { version } => match version.bugfix {
-            Some(_) => eprintln!("Only x.y Python versions can be upgraded, not x.y.z"),
-            None => download_python(&version, true)?,
+        Commands::Dependents { version } => print_dependents(&version)?,
+        Commands::Upgrade { version, pre } => match version.bugfix {
+            Some(_) => return Err(Error::UpgradeBugfix(version.to_string())),
+            None => {
+                download_python(
+                    &version,
+                    DownloadOptions {
+                        upgrade: true,
+                        pre,
+                        ..Default::default()
+                    },
+                )?;
+            }
         },
         Commands::SetProjectDirectory {
             project,
@@ -101,15 +507,51 @@ fn run() -> Result<(), Error> {
             set_project_directory(&project, &default_directory)?;
         }
         Commands::UnsetProjectDirectory { project } => unset_project_directory(&project)?,
-        Commands::SitePackages { project, version } => {
-            cd_site_packages(&project, &version)?;
+        Commands::SetHook { project, script } => set_hook(&project, &script)?,
+        Commands::UnsetHook { project } => unset_hook(&project)?,
+        Commands::SetAlias { alias, project } => set_alias(&alias, &project)?,
+        Commands::UnsetAlias { alias } => unset_alias(&alias)?,
+        Commands::Aliases => print_aliases()?,
+        Commands::Pip {
+            project,
+            version,
+            args,
+        } => {
+            let version = resolve_version(&project, version)?;
+            let status = run_pip(&version, &project, &args)?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        }
+        Commands::Freeze { project, version } => freeze_packages(&version, &project)?,
+        Commands::Restore { project, version } => restore_packages(&version, &project)?,
+        Commands::SitePackages {
+            project,
+            version,
+            shell,
+        } => {
+            let version = resolve_version(&project, version)?;
+            cd_site_packages(&project, &version, shell.as_deref())?;
         }
     }
     Ok(())
 }
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{e}");
+    let cli = Cli::parse();
+    logging::init(cli.verbose).expect("Nothing else sets a logger.");
+    http::set_offline(cli.offline);
+    releases::set_platform(cli.platform).expect("Nothing else sets the platform.");
+    // Upgrading is about getting the latest release, so don't trust the cache for it.
+    releases::set_refresh(cli.refresh || matches!(cli.cmd, Commands::Upgrade { .. }));
+    if let Err(e) = run(cli.cmd) {
+        match cli.json {
+            true => eprintln!(
+                "{}",
+                serde_json::json!({"error": e.to_string(), "kind": e.kind(), "code": e.exit_code()})
+            ),
+            false => eprintln!("{e}"),
+        }
+        std::process::exit(e.exit_code());
     }
 }
@@ -1,6 +1,15 @@
+use crate::directories::releases_cache_dir;
 use crate::error::Error;
+use crate::http::{is_offline, metadata_client, send};
 use crate::version::{parse_cpython_filename, parse_pypy_url, Version, PYPY_DOWNLOAD_URL};
+use chrono::{DateTime, Utc};
 use current_platform::CURRENT_PLATFORM;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 use url::Url;
 
 #[derive(Debug)]
@@ -9,55 +18,267 @@ pub struct Python {
     pub url: Url,
     pub version: Version,
     pub release_tag: String,
-    pub debug: bool,
-}
-
-pub async fn cpython_releases() -> Result<Vec<Python>, Error> {
-    let octocrab = octocrab::instance();
-    octocrab
-        .repos("indygreg", "python-build-standalone")
-        .releases()
-        .list()
-        .send()
-        .await?
-        .items
-        .into_iter()
-        .filter(|release| {
-            release.created_at
-                > Some(
-                    chrono::DateTime::parse_from_rfc3339("2022-02-26T00:00:00Z")
-                        .expect("Could not parse hardcoded datetime.")
-                        .into(),
-                )
+}
+
+static PLATFORM: OnceLock<String> = OnceLock::new();
+
+/// Download interpreters for `platform` instead of the platform lilyenv was built for.
+///
+/// The platform can only be set once; setting it again to a different platform fails.
+pub fn set_platform(platform: Option<String>) -> Result<(), Error> {
+    let Some(platform) = platform else {
+        return Ok(());
+    };
+    match PLATFORM.get_or_init(|| platform.clone()) {
+        current if *current == platform => Ok(()),
+        current => Err(Error::PlatformAlreadySet(current.clone())),
+    }
+}
+
+/// The target triple to download interpreters for.
+pub fn platform() -> &'static str {
+    PLATFORM.get().map_or(CURRENT_PLATFORM, String::as_str)
+}
+
+/// Whether interpreters are being downloaded for a different platform than this one.
+pub fn is_foreign_platform() -> bool {
+    platform() != CURRENT_PLATFORM
+}
+
+/// How long a cached release listing is used before it is fetched again.
+const RELEASE_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+static REFRESH: AtomicBool = AtomicBool::new(false);
+
+/// Ignore cached release listings and fetch them again.
+pub fn set_refresh(refresh: bool) {
+    REFRESH.store(refresh, Ordering::Relaxed);
+}
+
+fn releases_to_json(releases: &[Python]) -> serde_json::Value {
+    releases
+        .iter()
+        .map(|python| {
+            serde_json::json!({
+                "name": python.name,
+                "url": python.url.as_str(),
+                "version": python.version.to_string(),
+                "release_tag": python.release_tag,
+            })
         })
+        .collect()
+}
+
+fn releases_from_json(json: &serde_json::Value) -> Option<Vec<Python>> {
+    json.as_array()?
+        .iter()
+        .map(|python| {
+            Some(Python {
+                name: python["name"].as_str()?.to_string(),
+                url: Url::parse(python["url"].as_str()?).ok()?,
+                version: python["version"].as_str()?.parse().ok()?,
+                release_tag: python["release_tag"].as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Read a cached release listing, unless it is older than `ttl`.
+fn read_cache(path: &Path, ttl: Duration) -> Option<Vec<Python>> {
+    let age = std::fs::metadata(path).ok()?.modified().ok()?.elapsed();
+    // An outdated listing is better than none when offline.
+    if !is_offline() && age.map_or(true, |age| age > ttl) {
+        return None;
+    }
+    let json = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    releases_from_json(&json)
+}
+
+fn write_cache(path: &Path, releases: &[Python]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, releases_to_json(releases).to_string())?;
+    Ok(())
+}
+
+/// Use the release listing cached under `key`, or `fetch` it and cache it.
+fn cached_releases(
+    key: &str,
+    fetch: impl FnOnce() -> Result<Vec<Python>, Error>,
+) -> Result<Vec<Python>, Error> {
+    let path = releases_cache_dir()?.join(format!("{key}.json"));
+    if !REFRESH.load(Ordering::Relaxed) {
+        if let Some(releases) = read_cache(&path, RELEASE_CACHE_TTL) {
+            debug!("Using cached releases from {}", path.display());
+            return Ok(releases);
+        }
+    }
+    let releases = fetch()?;
+    if let Err(err) = write_cache(&path, &releases) {
+        warn!("Could not cache releases in {}: {err}", path.display());
+    }
+    Ok(releases)
+}
+
+const CPYTHON_REPO: &str = "astral-sh/python-build-standalone";
+const LEGACY_CPYTHON_REPO: &str = "indygreg/python-build-standalone";
+
+/// The GitHub repositories to try downloading CPython builds from, in `owner/name` form.
+fn cpython_repos(repo: Option<String>) -> Result<Vec<String>, Error> {
+    let repo = match repo {
+        Some(repo) => repo,
+        None => {
+            return Ok(vec![
+                CPYTHON_REPO.to_string(),
+                LEGACY_CPYTHON_REPO.to_string(),
+            ])
+        }
+    };
+    match repo.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok(vec![repo])
+        }
+        _ => Err(Error::InvalidRepository(repo)),
+    }
+}
+
+/// Parse a cutoff date like `2022-02-26` as midnight UTC.
+fn parse_cutoff(date: &str) -> Option<DateTime<Utc>> {
+    let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// The date before which CPython releases are ignored, from `LILYENV_CPYTHON_CUTOFF`.
+fn cpython_cutoff() -> Result<Option<DateTime<Utc>>, Error> {
+    match std::env::var("LILYENV_CPYTHON_CUTOFF") {
+        Ok(date) => match parse_cutoff(&date) {
+            Some(cutoff) => Ok(Some(cutoff)),
+            None => Err(Error::InvalidCutoff(date)),
+        },
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(err)?,
+    }
+}
+
+pub fn cpython_releases() -> Result<Vec<Python>, Error> {
+    let repo = match std::env::var("LILYENV_CPYTHON_REPO") {
+        Ok(repo) => Some(repo),
+        Err(std::env::VarError::NotPresent) => None,
+        Err(err) => Err(err)?,
+    };
+    let cutoff = cpython_cutoff()?;
+    for repo in cpython_repos(repo)? {
+        let mut key = format!("cpython-{}-{}", repo.replace('/', "-"), platform());
+        if let Some(cutoff) = cutoff {
+            key += &cutoff.format("-since-%Y%m%d").to_string();
+        }
+        let releases = cached_releases(&key, || repo_releases(&repo, cutoff))?;
+        if !releases.is_empty() {
+            return Ok(releases);
+        }
+    }
+    Ok(vec![])
+}
+
+/// The parts of a GitHub release that lilyenv reads.
+#[derive(Deserialize)]
+struct Release {
+    created_at: Option<DateTime<Utc>>,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: Url,
+}
+
+/// How many releases to ask GitHub for in each page of a listing.
+const RELEASES_PER_PAGE: usize = 100;
+
+/// List the CPython builds for this platform in a repository's releases.
+///
+/// GitHub lists releases newest first, a page at a time. Pages are fetched until one passes
+/// the cutoff or holds no builds lilyenv can use, which happens once the releases from before
+/// 2022, with an older naming scheme, are reached.
+fn repo_releases(repo: &str, cutoff: Option<DateTime<Utc>>) -> Result<Vec<Python>, Error> {
+    let client = metadata_client()?;
+    let mut pythons = vec![];
+    for page in 1.. {
+        let url = format!(
+            "https://api.github.com/repos/{repo}/releases?per_page={RELEASES_PER_PAGE}&page={page}"
+        );
+        debug!("Fetching {url}");
+        let releases = send(client.get(url))?
+            .error_for_status()?
+            .json::<Vec<Release>>()?;
+        let last_page = releases.len() < RELEASES_PER_PAGE;
+        let recent = releases
+            .into_iter()
+            .filter(|release| cutoff.is_none() || release.created_at > cutoff)
+            .collect::<Vec<_>>();
+        let passed_cutoff = recent.len() < RELEASES_PER_PAGE;
+        let builds = release_builds(recent);
+        if builds.is_empty() {
+            break;
+        }
+        pythons.extend(builds);
+        if last_page || passed_cutoff {
+            break;
+        }
+    }
+    Ok(pythons)
+}
+
+/// The CPython builds for this platform in `releases`.
+///
+/// Assets with names lilyenv can't parse are skipped.
+fn release_builds(releases: Vec<Release>) -> Vec<Python> {
+    releases
+        .into_iter()
         .flat_map(|release| release.assets)
         .filter(|asset| !asset.name.ends_with(".sha256"))
-        .filter(|asset| asset.name.contains(CURRENT_PLATFORM))
-        .map(|asset| {
-            let (release_tag, version) = parse_cpython_filename(&asset.name)?;
-            Ok(Python {
+        .filter(|asset| asset.name.contains(platform()))
+        .filter_map(|asset| match parse_cpython_filename(&asset.name) {
+            Ok((release_tag, version)) => Some(Python {
                 name: asset.name,
                 url: asset.browser_download_url,
                 version,
                 release_tag,
-                debug: version.debug,
-            })
+            }),
+            Err(err) => {
+                info!("Skipping {}: {err}", asset.name);
+                None
+            }
         })
         .collect()
 }
 
+/// The platform tag in the names of PyPy's downloads for this platform.
+///
+/// PyPy's Windows builds are `.zip` archives, which lilyenv can't extract, so Windows isn't
+/// supported.
 fn pypy_platform_tag() -> Result<&'static str, Error> {
-    match CURRENT_PLATFORM {
+    match platform() {
         "x86_64-unknown-linux-gnu" => Ok("linux64"),
         "x86_64-apple-darwin" => Ok("macos_x86_64"),
         "aarch64-unknown-linux-gnu" => Ok("aarch64"),
         "aarch64-apple-darwin" => Ok("macos_arm64"),
-        _ => Err(Error::Platform(CURRENT_PLATFORM.to_string())),
+        "i686-unknown-linux-gnu" => Ok("linux32"),
+        platform => Err(Error::Platform(platform.to_string())),
     }
 }
 
 pub fn pypy_releases() -> Result<Vec<Python>, Error> {
-    let html = reqwest::blocking::get("https://www.pypy.org/download.html")?.text()?;
+    let tag = pypy_platform_tag()?;
+    cached_releases(&format!("pypy-{tag}"), || scrape_pypy_releases(tag))
+}
+
+/// List the PyPy downloads linked from pypy.org for this platform, skipping any links lilyenv
+/// can't parse.
+fn scrape_pypy_releases(tag: &str) -> Result<Vec<Python>, Error> {
+    let html = send(metadata_client()?.get("https://www.pypy.org/download.html"))?.text()?;
     let document = scraper::Html::parse_document(&html);
     let selector = match scraper::Selector::parse("table>tbody>tr>td>p>a") {
         Ok(selector) => selector,
@@ -65,8 +286,7 @@ pub fn pypy_releases() -> Result<Vec<Python>, Error> {
             "Could not find table of pypy downloads.".to_string(),
         ))?,
     };
-    let tag = pypy_platform_tag()?;
-    document
+    Ok(document
         .select(&selector)
         .map(|link| {
             link.value()
@@ -75,15 +295,133 @@ pub fn pypy_releases() -> Result<Vec<Python>, Error> {
         })
         .filter(|link| link.starts_with(PYPY_DOWNLOAD_URL))
         .filter(|link| link.contains(tag))
-        .map(|url| {
-            let (name, release_tag, version) = parse_pypy_url(url)?;
-            Ok(Python {
-                name,
-                url: Url::parse(url)?,
-                version,
-                release_tag,
-                debug: false,
-            })
+        .filter_map(|url| match pypy_release(url) {
+            Ok(python) => Some(python),
+            Err(err) => {
+                info!("Skipping {url}: {err}");
+                None
+            }
         })
-        .collect()
+        .collect())
+}
+
+fn pypy_release(url: &str) -> Result<Python, Error> {
+    let (name, release_tag, version) = parse_pypy_url(url)?;
+    Ok(Python {
+        name,
+        url: Url::parse(url)?,
+        version,
+        release_tag,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpython_repos_default() {
+        assert_eq!(
+            cpython_repos(None).unwrap(),
+            vec![
+                "astral-sh/python-build-standalone",
+                "indygreg/python-build-standalone"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cpython_repos_configured() {
+        let repo = "example/python-mirror".to_string();
+        assert_eq!(
+            cpython_repos(Some(repo)).unwrap(),
+            vec!["example/python-mirror"]
+        );
+    }
+
+    #[test]
+    fn test_release_cache() {
+        let cache = tempfile::tempdir().unwrap();
+        let path = cache.path().join("releases").join("pypy-linux64.json");
+        assert!(read_cache(&path, RELEASE_CACHE_TTL).is_none());
+
+        let url = "https://downloads.python.org/pypy/pypy3.10-v7.3.15-linux64.tar.bz2";
+        let (name, release_tag, version) = parse_pypy_url(url).unwrap();
+        let python = Python {
+            name,
+            url: Url::parse(url).unwrap(),
+            version,
+            release_tag,
+        };
+        write_cache(&path, &[python]).unwrap();
+
+        let releases = read_cache(&path, RELEASE_CACHE_TTL).unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].name, "pypy3.10-v7.3.15-linux64.tar.bz2");
+        assert_eq!(releases[0].url.as_str(), url);
+        assert_eq!(releases[0].version.to_string(), "pypy3.10");
+        assert_eq!(releases[0].release_tag, "v7.3.15");
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(read_cache(&path, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_parse_cutoff() {
+        assert_eq!(
+            parse_cutoff("2022-02-26").unwrap().to_rfc3339(),
+            "2022-02-26T00:00:00+00:00"
+        );
+        for date in ["2022-02-30", "26/02/2022", "2022-02-26T00:00:00Z", ""] {
+            assert!(parse_cutoff(date).is_none());
+        }
+    }
+
+    #[test]
+    fn test_release_builds() {
+        let url = "https://github.com/astral-sh/python-build-standalone/releases/download/20240107";
+        let platform = platform();
+        let releases = serde_json::json!([{
+            "tag_name": "20240107",
+            "created_at": "2024-01-07T22:00:00Z",
+            "assets": [
+                {
+                    "name": format!("cpython-3.12.1+20240107-{platform}-install_only.tar.gz"),
+                    "browser_download_url": format!("{url}/cpython-3.12.1+20240107-{platform}-install_only.tar.gz"),
+                },
+                {
+                    "name": format!("cpython-3.12.1+20240107-{platform}-install_only.tar.gz.sha256"),
+                    "browser_download_url": format!("{url}/cpython-3.12.1+20240107-{platform}-install_only.tar.gz.sha256"),
+                },
+                {
+                    "name": format!("cpython-3.12.1-{platform}-debug-20240107.tar.zst"),
+                    "browser_download_url": format!("{url}/cpython-3.12.1-{platform}-debug-20240107.tar.zst"),
+                },
+            ],
+        }]);
+        let releases: Vec<Release> = serde_json::from_value(releases).unwrap();
+        let builds = release_builds(releases);
+        assert_eq!(builds.len(), 1);
+        assert_eq!(builds[0].release_tag, "20240107");
+        assert_eq!(builds[0].version.to_string(), "3.12.1");
+    }
+
+    #[test]
+    fn test_pypy_release() {
+        let url = format!("{PYPY_DOWNLOAD_URL}pypy3.10-v7.3.15-linux64.tar.bz2");
+        let python = pypy_release(&url).unwrap();
+        assert_eq!(python.name, "pypy3.10-v7.3.15-linux64.tar.bz2");
+        assert_eq!(python.release_tag, "v7.3.15");
+
+        let url = format!("{PYPY_DOWNLOAD_URL}pypy-nightly.tar.bz2");
+        assert!(matches!(pypy_release(&url), Err(Error::ParseAsset(_))));
+    }
+
+    #[test]
+    fn test_cpython_repos_invalid() {
+        for repo in ["python-mirror", "/python-mirror", "example/", "a/b/c"] {
+            let err = cpython_repos(Some(repo.to_string()));
+            assert!(matches!(err, Err(Error::InvalidRepository(_))));
+        }
+    }
 }
@@ -1,21 +1,143 @@
 use crate::directories::shell_file;
 use crate::error::Error;
 
-pub fn set_shell(shell: &str) -> Result<(), Error> {
-    std::fs::write(shell_file(), shell)?;
+const DEFAULT_SHELL: &str = "/bin/sh";
+const SUPPORTED_SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// Save the shell to use, checking that it is supported and installed unless `force` is set.
+pub fn set_shell(shell: &str, force: bool) -> Result<(), Error> {
+    if !force {
+        if !SUPPORTED_SHELLS.contains(&shell) {
+            return Err(Error::InvalidShell(
+                shell.to_string(),
+                SUPPORTED_SHELLS.join(", "),
+            ));
+        }
+        if find_executable(shell, &std::env::var("PATH").unwrap_or_default()).is_none() {
+            return Err(Error::ShellNotFound(shell.to_string()));
+        }
+    }
+    std::fs::write(shell_file()?, shell)?;
     Ok(())
 }
 
+/// Look for an executable called `name` in the directories of `path`, like `which`.
+pub fn find_executable(name: &str, path: &str) -> Option<std::path::PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|metadata| is_executable(&metadata))
+        })
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+/// Without permission bits to check, any file will do.
+#[cfg(not(unix))]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    metadata.is_file()
+}
+
+/// Set in the subshells lilyenv starts, so lilyenv run inside them uses the same shell.
+const SHELL_VARIABLE: &str = "LILYENV_SHELL";
+
 pub fn get_shell() -> Result<String, Error> {
-    match std::fs::read_to_string(shell_file()) {
+    match std::env::var(SHELL_VARIABLE) {
+        Ok(shell) if !shell.is_empty() => return Ok(shell),
+        Ok(_) | Err(std::env::VarError::NotPresent) => {}
+        Err(err) => Err(err)?,
+    }
+    match std::fs::read_to_string(shell_file()?) {
         Ok(shell) => Ok(shell),
         Err(err) => match err.kind() {
-            std::io::ErrorKind::NotFound => Ok(std::env::var("SHELL")?),
+            std::io::ErrorKind::NotFound => match std::env::var("SHELL") {
+                Ok(shell) => Ok(shell),
+                Err(std::env::VarError::NotPresent) => {
+                    Ok(login_shell().unwrap_or_else(|| DEFAULT_SHELL.to_string()))
+                }
+                Err(err) => Err(err)?,
+            },
             _ => Err(err)?,
         },
     }
 }
 
+/// Use the shell given for this invocation, falling back to the configured shell.
+pub fn resolve_shell(shell: Option<&str>) -> Result<String, Error> {
+    match shell {
+        Some(shell) => Ok(shell.to_string()),
+        None => get_shell(),
+    }
+}
+
+/// A command that starts `shell` as a subshell, marked so that nested lilyenv commands reuse it.
+pub fn subshell(shell: &str) -> std::process::Command {
+    let mut command = std::process::Command::new(shell);
+    command.env(SHELL_VARIABLE, shell);
+    command
+}
+
+/// Look up the current user's login shell in `/etc/passwd`.
+#[cfg(unix)]
+fn login_shell() -> Option<String> {
+    // SAFETY: getuid has no preconditions and always succeeds.
+    let uid = unsafe { libc::getuid() }.to_string();
+    let passwd = std::fs::read_to_string("/etc/passwd").ok()?;
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.get(2) == Some(&uid.as_str()))
+        .and_then(|fields| fields.get(6).map(|shell| shell.to_string()))
+        .filter(|shell| !shell.is_empty())
+}
+
+#[cfg(not(unix))]
+fn login_shell() -> Option<String> {
+    None
+}
+
+fn shell_name(shell: &str) -> &str {
+    std::path::Path::new(shell)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(shell)
+}
+
+/// Format a shell command that sets and exports an environment variable.
+pub fn export_variable(shell: &str, name: &str, value: &str) -> String {
+    match shell_name(shell) {
+        // fish treats variables ending in PATH as lists, so give each directory separately.
+        "fish" if name.ends_with("PATH") => {
+            let directories = value
+                .split(':')
+                .map(fish_quote)
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("set -gx {name} {directories};")
+        }
+        "fish" => format!("set -gx {name} {};", fish_quote(value)),
+        _ => format!("export {name}='{}';", value.replace('\'', "'\\''")),
+    }
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Format a shell command that unsets an environment variable.
+pub fn unset_variable(shell: &str, name: &str) -> String {
+    match shell_name(shell) {
+        "fish" => format!("set -e {name};"),
+        _ => format!("unset {name};"),
+    }
+}
+
 pub fn print_shell_config() -> Result<(), Error> {
     match get_shell()?.as_str() {
         "bash" => println!(include_str!("bash_config")),
@@ -25,3 +147,53 @@ pub fn print_shell_config() -> Result<(), Error> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_variable() {
+        assert_eq!(
+            export_variable("/bin/bash", "PATH", "/venv/bin:/usr/bin"),
+            "export PATH='/venv/bin:/usr/bin';"
+        );
+        assert_eq!(
+            export_variable("zsh", "VIRTUAL_ENV_PROMPT", "it's "),
+            "export VIRTUAL_ENV_PROMPT='it'\\''s ';"
+        );
+        assert_eq!(
+            export_variable("/usr/bin/fish", "PATH", "/venv/bin:/usr/bin"),
+            "set -gx PATH '/venv/bin' '/usr/bin';"
+        );
+        assert_eq!(
+            export_variable("fish", "LD_LIBRARY_PATH", "/python/lib"),
+            "set -gx LD_LIBRARY_PATH '/python/lib';"
+        );
+        assert_eq!(
+            export_variable("fish", "VIRTUAL_ENV_PROMPT", "it's "),
+            "set -gx VIRTUAL_ENV_PROMPT 'it\\'s ';"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_executable() {
+        use std::os::unix::fs::PermissionsExt;
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        std::fs::write(first.path().join("fish"), "").unwrap();
+        let zsh = second.path().join("zsh");
+        std::fs::write(&zsh, "").unwrap();
+        std::fs::set_permissions(&zsh, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = std::env::join_paths([first.path(), second.path()])
+            .unwrap()
+            .into_string()
+            .unwrap();
+
+        assert_eq!(find_executable("zsh", &path), Some(zsh));
+        // Not executable.
+        assert_eq!(find_executable("fish", &path), None);
+        assert_eq!(find_executable("bash", &path), None);
+    }
+}
@@ -23,6 +23,7 @@ pub struct Version {
     pub minor: u8,
     pub bugfix: Option<u8>,
     pub debug: bool,
+    pub freethreaded: bool,
     pub prerelease: PreRelease,
 }
 
@@ -35,6 +36,7 @@ impl Version {
                 && self.major == other.major
                 && self.minor == other.minor
                 && self.debug == other.debug
+                && self.freethreaded == other.freethreaded
                 && other.bugfix.is_none()
                 && self.prerelease == PreRelease::None
                 && other.prerelease == PreRelease::None
@@ -54,14 +56,23 @@ impl std::fmt::Display for Version {
             PreRelease::Beta(n) => format!("b{n}"),
             PreRelease::RC(n) => format!("rc{n}"),
         };
+        let freethreaded = match self.freethreaded {
+            false => "",
+            true => "t",
+        };
         let debug = match self.debug {
             false => "",
             true => "-debug",
         };
-        match self.bugfix {
-            Some(bugfix) => write!(f, "{}{}.{}.{}{}{}", prefix, self.major, self.minor, bugfix, prerelease, debug),
-            None => write!(f, "{}{}.{}{}", prefix, self.major, self.minor, debug),
-        }
+        let bugfix = match self.bugfix {
+            Some(bugfix) => format!(".{bugfix}"),
+            None => "".to_string(),
+        };
+        write!(
+            f,
+            "{}{}.{}{}{}{}{}",
+            prefix, self.major, self.minor, bugfix, prerelease, freethreaded, debug
+        )
     }
 }
 
@@ -102,6 +113,7 @@ fn parse_version(version: &str) -> nom::IResult<&str, Version> {
     let (rest, (major, minor)) = separated_pair(u8, tag("."), u8)(rest)?;
     let (rest, bugfix) = nom::combinator::opt(nom::sequence::preceded(tag("."), u8))(rest)?;
     let (rest, prerelease) = parse_prerelease(rest)?;
+    let (rest, freethreaded) = nom::combinator::opt(tag("t"))(rest)?;
     let (rest, debug) = nom::combinator::opt(tag("-debug"))(rest)?;
     let interpreter = match interpreter {
         Some(_) => Interpreter::PyPy,
@@ -115,6 +127,7 @@ fn parse_version(version: &str) -> nom::IResult<&str, Version> {
             minor,
             bugfix,
             debug: debug.is_some(),
+            freethreaded: freethreaded.is_some(),
             prerelease,
         },
     ))
@@ -126,10 +139,13 @@ fn _parse_cpython_filename(filename: &str) -> nom::IResult<&str, (String, Versio
     let (input, mut version) = parse_version(input)?;
     let (input, _) = tag("+")(input)?;
     let (input, release_tag) = nom::character::complete::digit1(input)?;
-    let (input, debug) = nom::combinator::opt(nom::bytes::complete::take_until("-debug"))(input)?;
-    if debug.is_some() {
+    // Build variants follow the platform, e.g. `-debug-full` or `-freethreaded+debug-full`.
+    if input.contains("-debug") || input.contains("+debug") {
         version.debug = true;
     }
+    if input.contains("-freethreaded") {
+        version.freethreaded = true;
+    }
     Ok((input, (release_tag.to_string(), version)))
 }
 
@@ -160,6 +176,15 @@ pub fn parse_pypy_url(url: &str) -> Result<(String, String, Version), Error> {
     }
 }
 
+/// Order PyPy release tags like `v7.3.15` by their numeric components.
+pub fn pypy_release_key(release_tag: &str) -> Vec<u32> {
+    release_tag
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -174,6 +199,7 @@ mod tests {
                 minor: 12,
                 bugfix: None,
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -186,6 +212,7 @@ mod tests {
                 minor: 12,
                 bugfix: Some(1),
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -198,6 +225,7 @@ mod tests {
                 minor: 10,
                 bugfix: None,
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -210,6 +238,7 @@ mod tests {
                 minor: 10,
                 bugfix: Some(4),
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -222,6 +251,7 @@ mod tests {
                 minor: 12,
                 bugfix: None,
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -234,6 +264,7 @@ mod tests {
                 minor: 12,
                 bugfix: Some(1),
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -246,6 +277,7 @@ mod tests {
                 minor: 10,
                 bugfix: None,
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -258,6 +290,7 @@ mod tests {
                 minor: 10,
                 bugfix: Some(4),
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -315,6 +348,7 @@ mod tests {
                 minor: 10,
                 bugfix: Some(13),
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -333,6 +367,7 @@ mod tests {
                 minor: 11,
                 bugfix: Some(9),
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
@@ -351,6 +386,7 @@ mod tests {
                 minor: 13,
                 bugfix: Some(0),
                 debug: true,
+                freethreaded: false,
                 prerelease: PreRelease::RC(2),
             }
         );
@@ -370,8 +406,128 @@ mod tests {
                 minor: 10,
                 bugfix: None,
                 debug: false,
+                freethreaded: false,
                 prerelease: PreRelease::None,
             }
         );
     }
+
+    #[test]
+    fn test_freethreaded_version_from_str() {
+        assert_eq!(
+            "3.13t".parse::<Version>().unwrap(),
+            Version {
+                interpreter: Interpreter::CPython,
+                major: 3,
+                minor: 13,
+                bugfix: None,
+                debug: false,
+                freethreaded: true,
+                prerelease: PreRelease::None,
+            }
+        );
+
+        assert_eq!(
+            "3.13.0rc1t".parse::<Version>().unwrap(),
+            Version {
+                interpreter: Interpreter::CPython,
+                major: 3,
+                minor: 13,
+                bugfix: Some(0),
+                debug: false,
+                freethreaded: true,
+                prerelease: PreRelease::RC(1),
+            }
+        );
+
+        assert_eq!(
+            "3.13.0rc10t-debug".parse::<Version>().unwrap(),
+            Version {
+                interpreter: Interpreter::CPython,
+                major: 3,
+                minor: 13,
+                bugfix: Some(0),
+                debug: true,
+                freethreaded: true,
+                prerelease: PreRelease::RC(10),
+            }
+        );
+
+        let version = "3.13-debugt";
+        let err = version.parse::<Version>();
+        assert!(matches!(err, Err(Error::InvalidVersion(_))));
+    }
+
+    #[test]
+    fn test_version_round_trip() {
+        let prereleases = [
+            PreRelease::None,
+            PreRelease::Alpha(1),
+            PreRelease::Beta(12),
+            PreRelease::RC(2),
+        ];
+        for interpreter in [Interpreter::CPython, Interpreter::PyPy] {
+            for bugfix in [None, Some(0), Some(12)] {
+                for prerelease in prereleases {
+                    for freethreaded in [false, true] {
+                        for debug in [false, true] {
+                            let version = Version {
+                                interpreter,
+                                major: 3,
+                                minor: 13,
+                                bugfix,
+                                debug,
+                                freethreaded,
+                                prerelease,
+                            };
+                            assert_eq!(version.to_string().parse::<Version>().unwrap(), version);
+                        }
+                    }
+                }
+            }
+        }
+        // Canonical strings are printed back unchanged.
+        for version in [
+            "3.13t",
+            "3.13t-debug",
+            "3.13.0t",
+            "3.13.0rc2t",
+            "3.13.0rc2t-debug",
+            "pypy3.10",
+            "pypy3.10.14-debug",
+        ] {
+            assert_eq!(version.parse::<Version>().unwrap().to_string(), version);
+        }
+    }
+
+    #[test]
+    fn test_parse_cpython_filename_freethreaded() {
+        let filename =
+            "cpython-3.13.0+20241008-x86_64-unknown-linux-gnu-freethreaded+pgo+lto-full.tar.zst";
+        let (release_tag, version) = parse_cpython_filename(filename).unwrap();
+        assert_eq!(release_tag, "20241008");
+        assert_eq!(version.to_string(), "3.13.0t");
+
+        let filename =
+            "cpython-3.13.0+20241008-x86_64-unknown-linux-gnu-freethreaded+debug-full.tar.zst";
+        let (release_tag, version) = parse_cpython_filename(filename).unwrap();
+        assert_eq!(release_tag, "20241008");
+        assert_eq!(version.to_string(), "3.13.0t-debug");
+    }
+
+    #[test]
+    fn test_parse_pypy_url_with_bugfix() {
+        let url = "https://downloads.python.org/pypy/pypy3.10.14-v7.3.17-aarch64.tar.bz2";
+        let (filename, release_tag, version) = parse_pypy_url(url).unwrap();
+        assert_eq!(filename, "pypy3.10.14-v7.3.17-aarch64.tar.bz2");
+        assert_eq!(release_tag, "v7.3.17");
+        assert_eq!(version.to_string(), "pypy3.10.14");
+    }
+
+    #[test]
+    fn test_pypy_release_key() {
+        let mut release_tags = ["v7.3.15", "v7.3.9", "v7.3.17", "v7.3.16"];
+        release_tags.sort_by_key(|release_tag| pypy_release_key(release_tag));
+        assert_eq!(release_tags, ["v7.3.9", "v7.3.15", "v7.3.16", "v7.3.17"]);
+    }
 }
@@ -1,56 +1,513 @@
-use crate::directories::{project_dir, project_file, python_dir, virtualenv_dir, virtualenvs_dir};
-use crate::download::download_python;
+use crate::directories::{
+    aliases_file, freeze_file, hook_file, interpreter_file, metadata_file, project_dir,
+    project_file, python_dir, virtualenv_dir, virtualenvs_dir,
+};
+use crate::download::{
+    download_python, downloaded_build, is_downloaded, python_executable, Build, DownloadOptions,
+};
 use crate::error::Error;
-use crate::shell::get_shell;
+use crate::http::is_offline;
+use crate::releases::{is_foreign_platform, platform};
+use crate::shell::{
+    export_variable, find_executable, get_shell, resolve_shell, subshell, unset_variable,
+};
 use crate::version::Version;
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
 
-pub fn create_virtualenv(version: &Version, project: &str) -> Result<(), Error> {
-    let python = python_dir(version);
-    if !python.exists() {
-        download_python(version, false)?;
-    }
-    let next = std::fs::read_dir(&python)?
-        .next()
-        .unwrap_or_else(|| {
-            panic!(
-                "Expected subdirectory missing from downloaded python at {:?}.",
-                &python
-            )
-        })?
-        .path();
-    let python_executable = next.join("bin/python3");
-    let virtualenv = virtualenv_dir(project, version);
-    std::process::Command::new(python_executable)
-        .arg("-m")
-        .arg("venv")
-        .arg(virtualenv)
-        .output()?;
+fn downloaded_python_executable(version: &Version) -> Result<PathBuf, Error> {
+    let python = python_dir(version)?;
+    if !is_downloaded(&python) {
+        if is_offline() {
+            return Err(Error::Offline(format!("download Python {version}")));
+        }
+        download_python(version, DownloadOptions::default())?;
+    }
+    let executable = python_executable(&python);
+    if !executable.is_file() {
+        return Err(Error::MissingInterpreter(version.to_string(), executable));
+    }
+    Ok(executable)
+}
+
+/// The tool used to create virtualenvs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The interpreter's built-in `venv` module.
+    #[default]
+    Venv,
+    /// The `virtualenv` command.
+    Virtualenv,
+    /// The `uv venv` command.
+    Uv,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Venv => "venv",
+            Self::Virtualenv => "virtualenv",
+            Self::Uv => "uv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Venv, Self::Virtualenv, Self::Uv]
+            .into_iter()
+            .find(|backend| backend.name() == name)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct VirtualenvOptions {
+    /// Create the virtualenv with this interpreter instead of a downloaded one.
+    pub python_path: Option<PathBuf>,
+    /// Remove any existing virtualenv first.
+    pub force: bool,
+    /// Extra arguments for the backend, like `--copies`.
+    pub venv_args: Vec<String>,
+    /// The tool that creates the virtualenv.
+    pub backend: Backend,
+}
+
+/// Use `backend` if its command is installed, and otherwise fall back to `venv`.
+fn available_backend(backend: Backend) -> Backend {
+    if backend == Backend::Venv
+        || find_executable(backend.name(), &std::env::var("PATH").unwrap_or_default()).is_some()
+    {
+        return backend;
+    }
+    warn!(
+        "Could not find {} on your PATH, so using venv instead.",
+        backend.name()
+    );
+    Backend::Venv
+}
+
+/// The command that creates a virtualenv at `virtualenv` with `backend`.
+fn venv_command(
+    backend: Backend,
+    python: &Path,
+    venv_args: &[String],
+    virtualenv: &Path,
+) -> std::process::Command {
+    let mut command = match backend {
+        Backend::Venv => {
+            let mut command = std::process::Command::new(python);
+            command.args(["-m", "venv"]);
+            command
+        }
+        Backend::Virtualenv => {
+            let mut command = std::process::Command::new("virtualenv");
+            command.arg("--python").arg(python);
+            command
+        }
+        Backend::Uv => {
+            // `--seed` installs pip, which lilyenv uses to manage packages.
+            let mut command = std::process::Command::new("uv");
+            command.args(["venv", "--seed", "--python"]).arg(python);
+            command
+        }
+    };
+    command.args(venv_args).arg(virtualenv);
+    command
+}
+
+/// Create a virtualenv at `virtualenv` with `backend`, letting it report its own errors.
+fn run_venv_command(
+    backend: Backend,
+    python: &Path,
+    venv_args: &[String],
+    virtualenv: &Path,
+) -> Result<(), Error> {
+    let status = venv_command(backend, python, venv_args, virtualenv).status()?;
+    if !status.success() {
+        let command = match backend {
+            Backend::Venv => "python -m venv",
+            Backend::Virtualenv => "virtualenv",
+            Backend::Uv => "uv venv",
+        };
+        return Err(Error::CommandFailed(command.to_string()));
+    }
     Ok(())
 }
 
+/// Replace the virtualenv at `virtualenv` with the one `create` makes there.
+///
+/// The old virtualenv is moved aside while `create` runs, and put back if it fails.
+fn replace_virtualenv(
+    virtualenv: &Path,
+    create: impl FnOnce() -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut name = virtualenv
+        .file_name()
+        .expect("A virtualenv directory has a file name.")
+        .to_os_string();
+    name.push(".old");
+    let old = virtualenv.with_file_name(name);
+    if old.exists() {
+        std::fs::remove_dir_all(&old)?;
+    }
+    debug!("Moving {} to {}", virtualenv.display(), old.display());
+    std::fs::rename(virtualenv, &old)?;
+    if let Err(err) = create() {
+        if virtualenv.exists() {
+            std::fs::remove_dir_all(virtualenv)?;
+        }
+        debug!("Moving {} back to {}", old.display(), virtualenv.display());
+        std::fs::rename(&old, virtualenv)?;
+        return Err(err);
+    }
+    info!("Removing the old virtualenv");
+    std::fs::remove_dir_all(&old)?;
+    Ok(())
+}
+
+pub fn create_virtualenv(
+    version: &Version,
+    project: &str,
+    options: &VirtualenvOptions,
+) -> Result<(), Error> {
+    let python_path = options.python_path.as_deref();
+    let python_executable = match python_path {
+        Some(python_path) => python_path.to_path_buf(),
+        None if is_foreign_platform() => return Err(Error::ForeignPlatform(platform().into())),
+        None => downloaded_python_executable(version)?,
+    };
+    let virtualenv = virtualenv_dir(project, version)?;
+    let backend = available_backend(options.backend);
+    info!(
+        "Creating {} with {} using {}",
+        virtualenv.display(),
+        python_executable.display(),
+        backend.name()
+    );
+    let create = || run_venv_command(backend, &python_executable, &options.venv_args, &virtualenv);
+    match options.force && virtualenv.exists() {
+        true => replace_virtualenv(&virtualenv, create)?,
+        false => create()?,
+    }
+    std::fs::write(
+        interpreter_file(project, version)?,
+        python_executable.display().to_string(),
+    )?;
+    let build = match python_path {
+        Some(_) => None,
+        None => downloaded_build(&python_dir(version)?),
+    };
+    let metadata = serde_json::json!({
+        "created": chrono::Local::now().to_rfc3339(),
+        "release_tag": build.as_ref().map(|build| &build.release_tag),
+        "interpreter_version": build
+            .as_ref()
+            .and_then(|build| build.version)
+            .map(|version| version.to_string()),
+        "python_path": python_path.map(|path| path.display().to_string()),
+        "venv_args": options.venv_args,
+        "backend": backend.name(),
+    });
+    std::fs::write(metadata_file(project, version)?, metadata.to_string())?;
+    Ok(())
+}
+
+/// The interpreter build recorded in a virtualenv's metadata, if it was a downloaded one.
+fn recorded_build(metadata: &serde_json::Value) -> Option<Build> {
+    Some(Build {
+        release_tag: metadata["release_tag"].as_str()?.to_string(),
+        version: metadata["interpreter_version"]
+            .as_str()
+            .and_then(|version| version.parse().ok()),
+    })
+}
+
+/// The metadata `create_virtualenv` recorded, if the virtualenv has any.
+fn virtualenv_metadata(project: &str, version: &Version) -> Option<serde_json::Value> {
+    let metadata = std::fs::read_to_string(metadata_file(project, version).ok()?).ok()?;
+    serde_json::from_str(&metadata).ok()
+}
+
+/// Point the `home`, `executable` and `base-executable` keys of `pyvenv.cfg` at `python`.
+fn repair_pyvenv_cfg(config: &str, python: &Path, executable: &Path) -> String {
+    let home = python
+        .parent()
+        .expect("A python executable is in a directory.");
+    config
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) => match key.trim() {
+                "home" => format!("home = {}", home.display()),
+                "executable" => format!("executable = {}", executable.display()),
+                "base-executable" => format!("base-executable = {}", executable.display()),
+                _ => line.to_string(),
+            },
+            None => line.to_string(),
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Point a virtualenv back at lilyenv's interpreter for its version, e.g. after lilyenv's
+/// data directory has moved.
+pub fn repair_virtualenv(project: &str, version: &Version) -> Result<(), Error> {
+    let virtualenv = virtualenv_dir(project, version)?;
+    let metadata = virtualenv_metadata(project, version);
+    if let Some(python_path) = metadata
+        .as_ref()
+        .and_then(|metadata| metadata["python_path"].as_str())
+    {
+        warn!("Not repairing {project} {version}, which was created with {python_path}.");
+        return Ok(());
+    }
+    let python = downloaded_python_executable(version)?;
+    let recorded = metadata.as_ref().and_then(recorded_build);
+    let current = downloaded_build(&python_dir(version)?);
+    if let (Some(recorded), Some(current)) = (recorded, current) {
+        if recorded != current {
+            warn!("{project} {version} was created with Python {recorded}, but Python {current} is downloaded now. Run `lilyenv reinstall {project} {version}` if it doesn't work.");
+        }
+    }
+    let executable = std::fs::canonicalize(&python)?;
+    info!(
+        "Repairing {} to use {}",
+        virtualenv.display(),
+        python.display()
+    );
+
+    let pyvenv_cfg = virtualenv.join("pyvenv.cfg");
+    let config = std::fs::read_to_string(&pyvenv_cfg)?;
+    // `home` is the old interpreter's `bin`, so its parent is the old python directory.
+    let old_python = config
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == "home")
+        .and_then(|(_, home)| Path::new(home.trim()).parent().map(Path::to_path_buf));
+    std::fs::write(
+        &pyvenv_cfg,
+        repair_pyvenv_cfg(&config, &python, &executable),
+    )?;
+
+    relink_interpreter(&virtualenv, &python, old_python.as_deref())?;
+    std::fs::write(
+        interpreter_file(project, version)?,
+        python.display().to_string(),
+    )?;
+    Ok(())
+}
+
+/// Point the interpreter links in a virtualenv's `bin` at `python`.
+///
+/// venv links `bin/python*` to the interpreter by absolute path, and the rest to those.
+#[cfg(unix)]
+fn relink_interpreter(
+    virtualenv: &Path,
+    python: &Path,
+    old_python: Option<&Path>,
+) -> Result<(), Error> {
+    for entry in std::fs::read_dir(virtualenv.join("bin"))? {
+        let link = entry?.path();
+        if link.is_symlink() && is_interpreter_link(&link, &std::fs::read_link(&link)?, old_python)
+        {
+            debug!("Relinking {}", link.display());
+            std::fs::remove_file(&link)?;
+            std::os::unix::fs::symlink(python, &link)?;
+        }
+    }
+    Ok(())
+}
+
+/// Elsewhere venv copies a launcher that finds the interpreter through `pyvenv.cfg`, so
+/// there are no links to fix.
+#[cfg(not(unix))]
+fn relink_interpreter(
+    _virtualenv: &Path,
+    _python: &Path,
+    _old_python: Option<&Path>,
+) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Whether the `bin` link at `link` to `target` points at the virtualenv's interpreter,
+/// rather than somewhere the user or a package chose.
+#[cfg(unix)]
+fn is_interpreter_link(link: &Path, target: &Path, old_python: Option<&Path>) -> bool {
+    if !target.is_absolute() {
+        return false;
+    }
+    let is_python = link
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("python"));
+    is_python || old_python.is_some_and(|old_python| target.starts_with(old_python))
+}
+
+pub fn repair_all_virtualenvs() -> Result<(), Error> {
+    let projects = match std::fs::read_dir(virtualenvs_dir()?) {
+        Ok(projects) => projects,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => Err(err)?,
+    };
+    for project in projects {
+        let project = project?;
+        let Some(name) = project_name(&project) else {
+            continue;
+        };
+        let versions = match list_versions(project.path()) {
+            Ok(versions) => versions,
+            Err(err) => {
+                warn!("Could not list the virtualenvs for {name}: {err}");
+                continue;
+            }
+        };
+        for version in versions {
+            if let Err(err) = repair_virtualenv(&name, &version.parse()?) {
+                warn!("Could not repair {name} {version}: {err}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of a project directory, or `None` with a warning if it isn't valid utf-8.
+fn project_name(project: &std::fs::DirEntry) -> Option<String> {
+    match project.file_name().into_string() {
+        Ok(name) => Some(name),
+        Err(name) => {
+            warn!(
+                "Skipping {}: its name isn't valid utf-8.",
+                name.to_string_lossy()
+            );
+            None
+        }
+    }
+}
+
 pub fn remove_virtualenv(project: &str, version: &Version) -> Result<(), Error> {
-    let virtualenv = virtualenv_dir(project, version);
+    let virtualenv = virtualenv_dir(project, version)?;
     std::fs::remove_dir_all(virtualenv)?;
     Ok(())
 }
 
 pub fn remove_project(project: &str) -> Result<(), Error> {
-    std::fs::remove_dir_all(project_dir(project))?;
+    std::fs::remove_dir_all(project_dir(project)?)?;
     Ok(())
 }
 
 pub fn set_project_directory(project: &str, default_directory: &str) -> Result<(), Error> {
-    std::fs::write(project_file(project), default_directory)?;
+    std::fs::write(project_file(project)?, default_directory)?;
     Ok(())
 }
 
 pub fn unset_project_directory(project: &str) -> Result<(), Error> {
-    std::fs::remove_file(project_file(project))?;
+    std::fs::remove_file(project_file(project)?)?;
+    Ok(())
+}
+
+/// Save a copy of `script` to be sourced by the shell when the project is activated.
+pub fn set_hook(project: &str, script: &Path) -> Result<(), Error> {
+    let hook = hook_file(project)?;
+    std::fs::create_dir_all(hook.parent().expect("The hook file is in a directory."))?;
+    std::fs::copy(script, hook)?;
+    Ok(())
+}
+
+pub fn unset_hook(project: &str) -> Result<(), Error> {
+    std::fs::remove_file(hook_file(project)?)?;
+    Ok(())
+}
+
+/// The `alias=project` lines of the aliases file, as pairs.
+fn parse_aliases(aliases: &str) -> Vec<(&str, &str)> {
+    aliases
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(alias, project)| (alias.trim(), project.trim()))
+        .collect()
+}
+
+/// The project `name` is an alias for, unless it is undefined or defined more than once.
+fn lookup_alias<'a>(aliases: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    let mut projects = aliases
+        .iter()
+        .filter(|(alias, _)| *alias == name)
+        .map(|(_, project)| *project)
+        .collect::<Vec<_>>();
+    projects.dedup();
+    match projects.as_slice() {
+        [project] => Some(project),
+        [] => None,
+        _ => {
+            warn!("{name} is an alias for several projects, so it is used as a project name.");
+            None
+        }
+    }
+}
+
+fn read_aliases() -> Result<String, Error> {
+    match std::fs::read_to_string(aliases_file()?) {
+        Ok(aliases) => Ok(aliases),
+        Err(err) => match err.kind() {
+            std::io::ErrorKind::NotFound => Ok(String::new()),
+            _ => Err(err)?,
+        },
+    }
+}
+
+/// The project `name` refers to: the project it is an alias for, or else `name` itself.
+pub fn resolve_alias(name: &str) -> Result<String, Error> {
+    let aliases = read_aliases()?;
+    match lookup_alias(&parse_aliases(&aliases), name) {
+        Some(project) => {
+            debug!("Using {project} for the alias {name}");
+            Ok(project.to_string())
+        }
+        None => Ok(name.to_string()),
+    }
+}
+
+fn write_aliases(aliases: &[(&str, &str)]) -> Result<(), Error> {
+    let aliases: String = aliases
+        .iter()
+        .map(|(alias, project)| format!("{alias}={project}\n"))
+        .collect();
+    let file = aliases_file()?;
+    std::fs::create_dir_all(file.parent().expect("The aliases file is in a directory."))?;
+    std::fs::write(file, aliases)?;
+    Ok(())
+}
+
+/// Save `alias` as a short name for `project`, replacing any existing alias of that name.
+pub fn set_alias(alias: &str, project: &str) -> Result<(), Error> {
+    if alias.is_empty() || alias.contains('=') || alias.contains(char::is_whitespace) {
+        return Err(Error::InvalidAlias(alias.to_string()));
+    }
+    let aliases = read_aliases()?;
+    let mut aliases = parse_aliases(&aliases);
+    aliases.retain(|(name, _)| *name != alias);
+    aliases.push((alias, project));
+    write_aliases(&aliases)
+}
+
+pub fn unset_alias(alias: &str) -> Result<(), Error> {
+    let aliases = read_aliases()?;
+    let mut aliases = parse_aliases(&aliases);
+    let count = aliases.len();
+    aliases.retain(|(name, _)| *name != alias);
+    if aliases.len() == count {
+        return Err(Error::AliasNotFound(alias.to_string()));
+    }
+    write_aliases(&aliases)
+}
+
+pub fn print_aliases() -> Result<(), Error> {
+    let aliases = read_aliases()?;
+    for (alias, project) in parse_aliases(&aliases) {
+        println!("{alias} -> {project}");
+    }
     Ok(())
 }
 
 fn project_directory(project: &str) -> Result<Option<String>, Error> {
-    match std::fs::read_to_string(project_file(project)) {
+    match std::fs::read_to_string(project_file(project)?) {
         Ok(default_directory) => Ok(Some(default_directory)),
         Err(err) => match err.kind() {
             std::io::ErrorKind::NotFound => Ok(None),
@@ -59,49 +516,502 @@ fn project_directory(project: &str) -> Result<Option<String>, Error> {
     }
 }
 
-pub fn activate_virtualenv(version: &Version, project: &str) -> Result<(), Error> {
-    let virtualenv = virtualenv_dir(project, version);
+/// Directories the system's terminfo database may be installed in.
+#[cfg(not(target_os = "macos"))]
+const TERMINFO_CANDIDATES: &[&str] = &[
+    "/etc/terminfo",
+    "/lib/terminfo",
+    "/usr/share/terminfo",
+    "/usr/lib/terminfo",
+    "/usr/local/share/terminfo",
+    "/run/current-system/sw/share/terminfo",
+];
+
+/// Directories the system's terminfo database may be installed in.
+#[cfg(target_os = "macos")]
+const TERMINFO_CANDIDATES: &[&str] = &[
+    "/usr/share/terminfo",
+    "/opt/homebrew/share/terminfo",
+    "/usr/local/share/terminfo",
+    "/run/current-system/sw/share/terminfo",
+];
+
+/// The variable the dynamic linker searches for the interpreter's shared libraries.
+///
+/// System Integrity Protection removes `DYLD_LIBRARY_PATH` when running protected programs
+/// like `/bin/zsh` on macOS, so there it only reaches programs started directly by lilyenv.
+const LIBRARY_PATH_VARIABLE: &str = match cfg!(target_os = "macos") {
+    true => "DYLD_LIBRARY_PATH",
+    false => "LD_LIBRARY_PATH",
+};
+
+/// The downloaded interpreters look for terminfo in their build location, so point them at
+/// the system's database instead, after any directories already in `TERMINFO_DIRS`.
+fn terminfo_dirs() -> String {
+    let mut dirs = match std::env::var("TERMINFO_DIRS") {
+        Ok(terminfo_dirs) => terminfo_dirs.split(':').map(String::from).collect(),
+        Err(_) => Vec::new(),
+    };
+    for &dir in TERMINFO_CANDIDATES {
+        if std::path::Path::new(dir).is_dir() && !dirs.iter().any(|existing| existing == dir) {
+            dirs.push(dir.to_string());
+        }
+    }
+    dirs.join(":")
+}
+
+/// Whether to set `TERMINFO_DIRS` and the library path for the downloaded interpreter.
+/// Setting `LILYENV_NO_INTERPRETER_ENV` turns this off.
+fn interpreter_env_enabled() -> bool {
+    match std::env::var("LILYENV_NO_INTERPRETER_ENV") {
+        Ok(value) => value.is_empty() || value == "0",
+        Err(_) => true,
+    }
+}
+
+/// Remove an already activated virtualenv's `bin` directory from `path`, so nested activations
+/// don't leave the outer virtualenv's executables available.
+fn without_active_virtualenv(path: &str) -> String {
+    let active_bin = match std::env::var_os("VIRTUAL_ENV") {
+        Some(virtualenv) => std::path::PathBuf::from(virtualenv).join("bin"),
+        None => return path.to_string(),
+    };
+    path.split(':')
+        .filter(|dir| std::path::Path::new(dir) != active_bin)
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Put `dir` in front of the directories in a `PATH`-like variable's `existing` value.
+fn prepend_path(dir: &Path, existing: Option<&str>) -> String {
+    match existing {
+        Some(existing) if !existing.is_empty() => format!("{}:{existing}", dir.display()),
+        _ => dir.display().to_string(),
+    }
+}
+
+/// Environment variables that may be set by `activation_env`.
+const ACTIVATION_VARIABLES: [&str; 5] = [
+    "VIRTUAL_ENV",
+    "VIRTUAL_ENV_PROMPT",
+    "PATH",
+    "TERMINFO_DIRS",
+    LIBRARY_PATH_VARIABLE,
+];
+
+/// Prefix for the variables `env` uses to remember pre-activation values.
+const STASH_PREFIX: &str = "_LILYENV_OLD_";
+
+/// The default `VIRTUAL_ENV_PROMPT` template.
+pub const DEFAULT_PROMPT: &str = "{project} ({version}) ";
+
+/// Fill in the `{project}` and `{version}` placeholders of a prompt template.
+fn render_prompt(template: &str, project: &str, version: &Version) -> String {
+    template
+        .replace("{project}", project)
+        .replace("{version}", &version.to_string())
+}
+
+fn activation_env(
+    version: &Version,
+    project: &str,
+    prompt: &str,
+) -> Result<Vec<(&'static str, String)>, Error> {
+    let virtualenv = virtualenv_dir(project, version)?;
     if !virtualenv.exists() {
-        create_virtualenv(version, project)?
+        create_virtualenv(version, project, &VirtualenvOptions::default())?
+    }
+    // An interpreter from `--python-path` has its own library directory, if it needs one.
+    let external = virtualenv_metadata(project, version)
+        .is_some_and(|metadata| metadata["python_path"].is_string());
+    let lib = match external {
+        true => None,
+        false => Some(python_dir(version)?.join("python").join("lib")),
+    };
+    virtualenv_env(
+        &virtualenv,
+        lib.as_deref(),
+        render_prompt(prompt, project, version),
+    )
+}
+
+/// The environment variables that activate the virtualenv at `virtualenv`.
+///
+/// `lib` is the library directory of a lilyenv-managed interpreter, added to the library path.
+fn virtualenv_env(
+    virtualenv: &Path,
+    lib: Option<&Path>,
+    prompt: String,
+) -> Result<Vec<(&'static str, String)>, Error> {
+    let path = without_active_virtualenv(&std::env::var("PATH")?);
+    let path = prepend_path(&virtualenv.join("bin"), Some(&path));
+    let mut env = vec![
+        ("VIRTUAL_ENV", virtualenv.display().to_string()),
+        ("VIRTUAL_ENV_PROMPT", prompt),
+        ("PATH", path),
+    ];
+    if interpreter_env_enabled() {
+        env.push(("TERMINFO_DIRS", terminfo_dirs()));
+        if let Some(lib) = lib {
+            let library_path = std::env::var(LIBRARY_PATH_VARIABLE).ok();
+            let library_path = prepend_path(lib, library_path.as_deref());
+            env.push((LIBRARY_PATH_VARIABLE, library_path));
+        }
     }
-    let path = std::env::var("PATH")?;
-    let path = format!("{}:{path}", virtualenv.join("bin").display());
+    Ok(env)
+}
+
+pub fn activate_virtualenv(
+    version: &Version,
+    project: &str,
+    directory: Option<&str>,
+    save_directory: bool,
+    shell: Option<&str>,
+    prompt: &str,
+) -> Result<(), Error> {
+    let env = activation_env(version, project, prompt)?;
+    let directory = match directory {
+        Some(directory) => {
+            let directory = std::fs::canonicalize(directory)?;
+            let directory = directory
+                .to_str()
+                .expect("The directory should be valid unicode.")
+                .to_string();
+            if save_directory {
+                set_project_directory(project, &directory)?;
+            }
+            Some(directory)
+        }
+        None => project_directory(project)?,
+    };
 
-    let mut shell = std::process::Command::new(get_shell()?);
-    let shell = match project_directory(project)? {
+    let mut shell = subshell(&resolve_shell(shell)?);
+    // The `shell-config` snippet sources this once the shell starts.
+    let hook = hook_file(project)?;
+    if hook.is_file() {
+        shell.env("LILYENV_ACTIVATE_HOOK", hook);
+    }
+    let shell = match directory {
         Some(directory) => shell.current_dir(directory),
         _ => &mut shell,
     };
-    let python = python_dir(version).join("python");
-    let mut shell = shell
-        .env("VIRTUAL_ENV", &virtualenv)
-        .env("VIRTUAL_ENV_PROMPT", format!("{project} ({version}) "))
-        .env("PATH", path)
-        .env(
-            "TERMINFO_DIRS",
-            "/etc/terminfo:/lib/terminfo:/usr/share/terminfo",
-        )
-        .env("LD_LIBRARY_PATH", python.join("lib"))
-        .spawn()?;
+    let mut shell = shell.envs(env).spawn()?;
     shell.wait()?;
     Ok(())
 }
 
-pub fn cd_site_packages(project: &str, version: &Version) -> Result<(), Error> {
-    let virtualenv = virtualenv_dir(project, version);
-    let lib = virtualenv.join("lib");
-    let next = std::fs::read_dir(&lib)?
-        .next()
-        .unwrap_or_else(|| {
-            panic!(
-                "Expected subdirectory missing from virtualenv at {:?}.",
-                &lib
-            )
-        })?
-        .path();
-    let site_packages = next.join("site-packages");
+/// Activate a throwaway virtualenv in a temporary directory, removing it when the shell exits.
+///
+/// The interpreter is downloaded as usual, so it is still there for the next one.
+pub fn activate_scratch_virtualenv(
+    version: &Version,
+    shell: Option<&str>,
+    prompt: &str,
+) -> Result<(), Error> {
+    if is_foreign_platform() {
+        return Err(Error::ForeignPlatform(platform().into()));
+    }
+    let python_executable = downloaded_python_executable(version)?;
+    // Removed when dropped, whether or not the shell succeeds.
+    let scratch = tempfile::Builder::new()
+        .prefix("lilyenv-scratch-")
+        .tempdir()?;
+    let backend = available_backend(Backend::default());
+    info!(
+        "Creating {} with {} using {}",
+        scratch.path().display(),
+        python_executable.display(),
+        backend.name()
+    );
+    run_venv_command(backend, &python_executable, &[], scratch.path())?;
+    let lib = python_dir(version)?.join("python").join("lib");
+    let env = virtualenv_env(
+        scratch.path(),
+        Some(&lib),
+        render_prompt(prompt, "scratch", version),
+    )?;
+    subshell(&resolve_shell(shell)?).envs(env).spawn()?.wait()?;
+    info!("Removing {}", scratch.path().display());
+    scratch.close()?;
+    Ok(())
+}
+
+pub fn print_activation_env(version: &Version, project: &str, prompt: &str) -> Result<(), Error> {
+    let shell = get_shell()?;
+    let env = activation_env(version, project, prompt)?;
+    if std::env::var_os(format!("{STASH_PREFIX}PATH")).is_none() {
+        // Stash all of them, so `deactivate` restores any that this activation leaves alone.
+        for name in ACTIVATION_VARIABLES {
+            if let Ok(value) = std::env::var(name) {
+                let stash = format!("{STASH_PREFIX}{name}");
+                println!("{}", export_variable(&shell, &stash, &value));
+            }
+        }
+    }
+    for (name, value) in env {
+        println!("{}", export_variable(&shell, name, &value));
+    }
+    Ok(())
+}
+
+/// Build a standalone POSIX shell script that activates a virtualenv when sourced, along
+/// with a `deactivate` function to undo it.
+fn activation_script(project: &str, version: &Version, env: &[(&str, String)]) -> String {
+    let mut script = format!(
+        "# Activates the {project} {version} virtualenv. Generated by lilyenv; use with `. <file>`.\n"
+    );
+    for (name, _) in env {
+        script +=
+            &format!("if [ -n \"${{{name}+set}}\" ]; then {STASH_PREFIX}{name}=\"${name}\"; fi\n");
+    }
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "'\\''"));
+    for (name, value) in env {
+        // Keep the paths of whoever sources the script, rather than the ones here.
+        let first = value.split(':').next().unwrap_or_default();
+        let line = match *name {
+            "PATH" => format!("export PATH={}:\"$PATH\";", quote(first)),
+            // An empty entry would mean the current directory, so only add `:` if it is set.
+            LIBRARY_PATH_VARIABLE => {
+                format!("export {name}={}\"${{{name}:+:${name}}}\";", quote(first))
+            }
+            "TERMINFO_DIRS" => format!(
+                "export TERMINFO_DIRS=\"${{TERMINFO_DIRS:+$TERMINFO_DIRS:}}\"{};",
+                quote(&TERMINFO_CANDIDATES.join(":"))
+            ),
+            _ => export_variable("sh", name, value),
+        };
+        script += &line;
+        script += "\n";
+    }
+    script += "deactivate() {\n";
+    for (name, _) in env {
+        let stash = format!("{STASH_PREFIX}{name}");
+        script += &format!(
+            "    if [ -n \"${{{stash}+set}}\" ]; then export {name}=\"${stash}\"; unset {stash}; else unset {name}; fi\n"
+        );
+    }
+    script += "    unset -f deactivate\n}\n";
+    script
+}
+
+pub fn print_activation_script(
+    version: &Version,
+    project: &str,
+    prompt: &str,
+) -> Result<(), Error> {
+    let env = activation_env(version, project, prompt)?;
+    print!("{}", activation_script(project, version, &env));
+    Ok(())
+}
+
+/// Find the project and version of a virtualenv from its path under `virtualenvs`.
+fn virtualenv_from_path(virtualenvs: &Path, path: &Path) -> Option<(String, Version)> {
+    let mut parts = path.strip_prefix(virtualenvs).ok()?.iter();
+    let project = parts.next()?.to_str()?.to_string();
+    let version = parts.next()?.to_str()?.parse().ok()?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some((project, version)),
+    }
+}
+
+/// Print the prompt label of the active lilyenv virtualenv, or nothing if there isn't one.
+pub fn print_prompt(prompt: &str) -> Result<(), Error> {
+    let virtualenv = match std::env::var_os("VIRTUAL_ENV") {
+        Some(virtualenv) => PathBuf::from(virtualenv),
+        None => return Ok(()),
+    };
+    if let Some((project, version)) = virtualenv_from_path(&virtualenvs_dir()?, &virtualenv) {
+        print!("{}", render_prompt(prompt, &project, &version));
+    }
+    Ok(())
+}
 
-    let mut shell = std::process::Command::new(get_shell()?)
+pub fn print_deactivation_env() -> Result<(), Error> {
+    if std::env::var_os(format!("{STASH_PREFIX}PATH")).is_none() {
+        return Err(Error::NotActivated);
+    }
+    let shell = get_shell()?;
+    for name in ACTIVATION_VARIABLES {
+        let stash = format!("{STASH_PREFIX}{name}");
+        match std::env::var(&stash) {
+            Ok(value) => {
+                println!("{}", export_variable(&shell, name, &value));
+                println!("{}", unset_variable(&shell, &stash));
+            }
+            Err(_) => println!("{}", unset_variable(&shell, name)),
+        }
+    }
+    Ok(())
+}
+
+fn pip_command(version: &Version, project: &str) -> Result<std::process::Command, Error> {
+    let virtualenv = virtualenv_dir(project, version)?;
+    if !virtualenv.exists() {
+        create_virtualenv(version, project, &VirtualenvOptions::default())?
+    }
+    let mut pip = std::process::Command::new(virtualenv.join("bin").join("pip"));
+    pip.env("VIRTUAL_ENV", &virtualenv);
+    Ok(pip)
+}
+
+pub fn run_pip(
+    version: &Version,
+    project: &str,
+    args: &[String],
+) -> Result<std::process::ExitStatus, Error> {
+    Ok(pip_command(version, project)?.args(args).status()?)
+}
+
+pub fn freeze_packages(version: &Version, project: &str) -> Result<(), Error> {
+    freeze_to(version, project, &freeze_file(project, version)?)
+}
+
+/// Write the packages installed in a virtualenv to the requirements file `path`.
+fn freeze_to(version: &Version, project: &str, path: &Path) -> Result<(), Error> {
+    let output = pip_command(version, project)?
+        .arg("freeze")
+        .stderr(std::process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::CommandFailed("pip freeze".to_string()));
+    }
+    std::fs::write(path, output.stdout)?;
+    Ok(())
+}
+
+pub fn restore_packages(version: &Version, project: &str) -> Result<(), Error> {
+    let requirements = freeze_file(project, version)?;
+    if !requirements.exists() {
+        return Err(Error::NotFrozen(format!("{project} {version}")));
+    }
+    install_requirements(version, project, &requirements)
+}
+
+fn install_requirements(version: &Version, project: &str, path: &Path) -> Result<(), Error> {
+    let status = pip_command(version, project)?
+        .arg("install")
+        .arg("-r")
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(Error::CommandFailed("pip install".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Lockfile {
+    /// A `uv.lock` file, which only `uv` can install.
+    Uv,
+    /// A requirements file pinned with `--hash` options, e.g. from `pip-compile --generate-hashes`.
+    HashedRequirements,
+    Requirements,
+}
+
+fn lockfile_kind(path: &Path, contents: &str) -> Lockfile {
+    if path.file_name().is_some_and(|name| name == "uv.lock")
+        || (contents.starts_with("version =") && contents.contains("[[package]]"))
+    {
+        Lockfile::Uv
+    } else if contents.contains("--hash=") {
+        Lockfile::HashedRequirements
+    } else {
+        Lockfile::Requirements
+    }
+}
+
+/// Install the packages pinned in a lockfile into a virtualenv.
+pub fn install_lockfile(version: &Version, project: &str, lock: &Path) -> Result<(), Error> {
+    let contents = std::fs::read_to_string(lock)?;
+    let (command, status) = match lockfile_kind(lock, &contents) {
+        Lockfile::Uv => {
+            let directory = std::fs::canonicalize(lock)?
+                .parent()
+                .expect("A lockfile is in a directory.")
+                .to_path_buf();
+            let status = std::process::Command::new("uv")
+                .args(["sync", "--frozen"])
+                .current_dir(directory)
+                .env("UV_PROJECT_ENVIRONMENT", virtualenv_dir(project, version)?)
+                .status();
+            match status {
+                Ok(status) => ("uv sync", status),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::MissingUv(lock.to_path_buf()))
+                }
+                Err(err) => Err(err)?,
+            }
+        }
+        kind => {
+            let mut pip = pip_command(version, project)?;
+            pip.arg("install");
+            if kind == Lockfile::HashedRequirements {
+                pip.arg("--require-hashes");
+            }
+            ("pip install", pip.arg("-r").arg(lock).status()?)
+        }
+    };
+    if !status.success() {
+        return Err(Error::CommandFailed(command.to_string()));
+    }
+    Ok(())
+}
+
+/// Recreate a virtualenv with the same interpreter, keeping its installed packages.
+pub fn reinstall_virtualenv(version: &Version, project: &str) -> Result<(), Error> {
+    // Freeze to a temporary file, so a snapshot saved with `lilyenv freeze` is left alone.
+    let frozen = tempfile::NamedTempFile::new()?;
+    let requirements = match freeze_to(version, project, frozen.path()) {
+        Ok(()) => frozen.path().to_path_buf(),
+        Err(err) => {
+            let saved = freeze_file(project, version)?;
+            if !saved.exists() {
+                return Err(err);
+            }
+            warn!("Could not freeze {project} {version} ({err}), so reinstalling the packages saved earlier.");
+            saved
+        }
+    };
+    let metadata = virtualenv_metadata(project, version).unwrap_or_default();
+    let options = VirtualenvOptions {
+        python_path: metadata["python_path"].as_str().map(PathBuf::from),
+        force: true,
+        venv_args: metadata["venv_args"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|arg| arg.as_str().map(String::from))
+            .collect(),
+        backend: metadata["backend"]
+            .as_str()
+            .and_then(Backend::from_name)
+            .unwrap_or_default(),
+    };
+    create_virtualenv(version, project, &options)?;
+    install_requirements(version, project, &requirements)
+}
+
+/// Find `lib/pythonX.Y/site-packages`, ignoring any other entries in `lib`.
+fn site_packages_dir(project: &str, version: &Version) -> Result<PathBuf, Error> {
+    let lib = virtualenv_dir(project, version)?.join("lib");
+    std::fs::read_dir(&lib)?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|dir| dir.file_name().to_string_lossy().starts_with("python"))
+        .map(|dir| dir.path().join("site-packages"))
+        .find(|site_packages| site_packages.is_dir())
+        .ok_or(Error::MissingSitePackages(lib))
+}
+
+pub fn cd_site_packages(
+    project: &str,
+    version: &Version,
+    shell: Option<&str>,
+) -> Result<(), Error> {
+    let site_packages = site_packages_dir(project, version)?;
+
+    let mut shell = subshell(&resolve_shell(shell)?)
         .current_dir(site_packages)
         .spawn()?;
     shell.wait()?;
@@ -109,34 +1019,178 @@ pub fn cd_site_packages(project: &str, version: &Version) -> Result<(), Error> {
 }
 
 fn list_versions(path: std::path::PathBuf) -> Result<Vec<String>, Error> {
-    Ok(std::fs::read_dir(path)?
+    let mut versions = vec![];
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let version = match entry.file_name().into_string() {
+            Ok(version) => version,
+            Err(name) => {
+                warn!(
+                    "Skipping {}: its name isn't valid utf-8.",
+                    name.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        if version.parse::<Version>().is_ok() {
+            versions.push(version);
+        }
+    }
+    Ok(versions)
+}
+
+/// Use the given version, or the project's only virtualenv if no version was given, falling
+/// back to `LILYENV_PYTHON`.
+pub fn resolve_version(project: &str, version: Option<Version>) -> Result<Version, Error> {
+    if let Some(version) = version {
+        return Ok(version);
+    }
+    let versions = match list_versions(project_dir(project)?) {
+        Ok(versions) => versions,
+        Err(Error::Fs(err)) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(err) => return Err(err),
+    };
+    if let [version] = versions.as_slice() {
+        return version.parse();
+    }
+    if let Some(version) = default_version()? {
+        return Ok(version);
+    }
+    if versions.is_empty() {
+        return Err(Error::NoVirtualenvs(project.to_string()));
+    }
+    if let Some(version) = prompt_for_version(project, &versions)? {
+        return version.parse();
+    }
+    Err(Error::AmbiguousVersion(
+        project.to_string(),
+        versions.join(" "),
+    ))
+}
+
+/// Ask which version to use, if there's a terminal to ask on.
+fn prompt_for_version(project: &str, versions: &[String]) -> Result<Option<String>, Error> {
+    use std::io::{BufRead, IsTerminal, Write};
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return Ok(None);
+    }
+    eprintln!("{project} has several virtualenvs:");
+    for (number, version) in versions.iter().enumerate() {
+        eprintln!("  {}) {version}", number + 1);
+    }
+    let mut input = String::new();
+    loop {
+        eprint!("Choose a version: ");
+        std::io::stderr().flush()?;
+        input.clear();
+        if std::io::stdin().lock().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        match pick_version(versions, &input) {
+            Some(version) => return Ok(Some(version)),
+            None => eprintln!("Enter a number from 1 to {} or a version.", versions.len()),
+        }
+    }
+}
+
+/// Match the user's answer against the menu, either by number or by version.
+fn pick_version(versions: &[String], input: &str) -> Option<String> {
+    let input = input.trim();
+    match input.parse::<usize>() {
+        Ok(number) => versions.get(number.checked_sub(1)?).cloned(),
+        Err(_) => versions.iter().find(|version| *version == input).cloned(),
+    }
+}
+
+/// The version set in `LILYENV_PYTHON`, used when nothing more specific picks one.
+fn default_version() -> Result<Option<Version>, Error> {
+    match std::env::var("LILYENV_PYTHON") {
+        Ok(version) => Ok(Some(version.parse()?)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(err)?,
+    }
+}
+
+/// Count the packages installed in a virtualenv by their `.dist-info` directories.
+fn package_count(project: &str, version: &Version) -> Result<usize, Error> {
+    Ok(std::fs::read_dir(site_packages_dir(project, version)?)?
         .collect::<Result<Vec<_>, _>>()?
         .into_iter()
-        .filter(|version| {
-            version
-                .file_type()
-                .expect("Could not read file type.")
-                .is_dir()
-        })
-        .map(|version| {
-            version
-                .file_name()
-                .to_str()
-                .expect("Could not convert a version to utf-8.")
-                .to_string()
-        })
-        .collect::<Vec<_>>())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".dist-info"))
+        .filter(|entry| entry.path().is_dir())
+        .count())
+}
+
+/// Print one line per virtualenv with its interpreter, creation date and package count.
+fn print_virtualenv_details(project: &str, versions: &[String], indent: &str) -> Result<(), Error> {
+    for name in versions {
+        let version = name.parse::<Version>()?;
+        let interpreter = match virtualenv_interpreter(project, &version)? {
+            Some(interpreter) => interpreter.display().to_string(),
+            None => "unknown interpreter".to_string(),
+        };
+        let metadata = virtualenv_metadata(project, &version);
+        let recorded = metadata
+            .as_ref()
+            .and_then(|metadata| metadata["created"].as_str())
+            .and_then(|created| chrono::DateTime::parse_from_rfc3339(created).ok());
+        let created = match recorded {
+            Some(created) => created.with_timezone(&chrono::Local),
+            // Virtualenvs from older versions of lilyenv have no metadata.
+            None => std::fs::metadata(virtualenv_dir(project, &version)?)?
+                .modified()?
+                .into(),
+        };
+        let created = created.format("%Y-%m-%d %H:%M");
+        let release = match metadata.as_ref().map(recorded_build) {
+            Some(Some(build)) => format!(" {build}"),
+            _ => "".to_string(),
+        };
+        let packages = match package_count(project, &version) {
+            Ok(count) => format!("{count} packages"),
+            Err(_) => "unknown packages".to_string(),
+        };
+        println!("{indent}{name}  {interpreter}{release}  {created}  {packages}");
+    }
+    Ok(())
+}
+
+/// The project's name, followed by its default directory if `project_paths` is set and it has one.
+fn project_label(project: &str, project_paths: bool) -> Result<String, Error> {
+    match project_paths {
+        true => match project_directory(project)? {
+            Some(directory) => Ok(format!("{project} ({directory})")),
+            None => Ok(project.to_string()),
+        },
+        false => Ok(project.to_string()),
+    }
 }
 
-pub fn print_project_versions(project: String) -> Result<(), Error> {
-    let virtualenvs = project_dir(&project);
+pub fn print_project_versions(
+    project: String,
+    long: bool,
+    project_paths: bool,
+) -> Result<(), Error> {
+    let virtualenvs = project_dir(&project)?;
     let versions = list_versions(virtualenvs)?;
-    println!("{}", versions.join(" "));
+    if project_paths {
+        if let Some(directory) = project_directory(&project)? {
+            println!("Directory: {directory}");
+        }
+    }
+    if long {
+        print_virtualenv_details(&project, &versions, "")?;
+    } else {
+        println!("{}", versions.join(" "));
+    }
     Ok(())
 }
 
-pub fn print_all_versions() -> Result<(), Error> {
-    let projects = virtualenvs_dir();
+pub fn print_all_versions(long: bool, project_paths: bool) -> Result<(), Error> {
+    let projects = virtualenvs_dir()?;
     let projects = match std::fs::read_dir(projects) {
         Ok(projects) => projects,
         Err(err) => match err.kind() {
@@ -151,15 +1205,380 @@ pub fn print_all_versions() -> Result<(), Error> {
     };
     for project in projects {
         let project = project?;
-        let versions = list_versions(project.path())?;
-        println!(
-            "{}: {}",
-            project
-                .file_name()
-                .to_str()
-                .expect("Could not convert a project directory name to utf-8"),
-            versions.join(" ")
-        );
+        let name = project.file_name().to_string_lossy().to_string();
+        // Keep listing the other projects if one can't be read.
+        if let Err(err) = print_project_line(&project.path(), &name, long, project_paths) {
+            warn!("Could not list the virtualenvs for {name}: {err}");
+        }
+    }
+    Ok(())
+}
+
+fn print_project_line(
+    project_dir: &Path,
+    name: &str,
+    long: bool,
+    project_paths: bool,
+) -> Result<(), Error> {
+    let versions = list_versions(project_dir.to_path_buf())?;
+    let label = project_label(name, project_paths)?;
+    if long {
+        println!("{label}:");
+        print_virtualenv_details(name, &versions, "  ")?;
+    } else {
+        println!("{label}: {}", versions.join(" "));
+    }
+    Ok(())
+}
+
+/// The interpreter a virtualenv was created with, from lilyenv's record or `pyvenv.cfg`.
+fn virtualenv_interpreter(project: &str, version: &Version) -> Result<Option<PathBuf>, Error> {
+    match std::fs::read_to_string(interpreter_file(project, version)?) {
+        Ok(interpreter) => return Ok(Some(PathBuf::from(interpreter))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => Err(err)?,
+    }
+    match std::fs::read_to_string(virtualenv_dir(project, version)?.join("pyvenv.cfg")) {
+        Ok(config) => Ok(config
+            .lines()
+            .find_map(|line| line.strip_prefix("home = "))
+            .map(PathBuf::from)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err)?,
+    }
+}
+
+pub fn print_dependents(version: &Version) -> Result<(), Error> {
+    let python = python_dir(version)?;
+    let projects = match std::fs::read_dir(virtualenvs_dir()?) {
+        Ok(projects) => projects,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => Err(err)?,
+    };
+    for project in projects {
+        let project = project?;
+        if !project.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(name) = project_name(&project) else {
+            continue;
+        };
+        // Keep checking the other projects if one can't be read.
+        if let Err(err) = print_project_dependents(&project.path(), &name, &python) {
+            warn!("Could not check the virtualenvs for {name}: {err}");
+        }
+    }
+    Ok(())
+}
+
+fn print_project_dependents(project_dir: &Path, name: &str, python: &Path) -> Result<(), Error> {
+    for virtualenv_version in list_versions(project_dir.to_path_buf())? {
+        let virtualenv_version = virtualenv_version
+            .parse::<Version>()
+            .expect("list_versions only returns valid versions.");
+        if let Some(interpreter) = virtualenv_interpreter(name, &virtualenv_version)? {
+            if interpreter.starts_with(python) {
+                println!("{name} {virtualenv_version}");
+            }
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_kind() {
+        let uv_lock = "version = 1\nrequires-python = \">=3.12\"\n\n[[package]]\nname = \"idna\"\n";
+        assert_eq!(lockfile_kind(Path::new("uv.lock"), ""), Lockfile::Uv);
+        assert_eq!(lockfile_kind(Path::new("deps.lock"), uv_lock), Lockfile::Uv);
+        assert_eq!(
+            lockfile_kind(
+                Path::new("requirements.lock"),
+                "idna==3.7 \\\n    --hash=sha256:82fee1fc78add43492d3a1898bfa6d8a904cc97d8427f683ed8e798d07761aa0\n"
+            ),
+            Lockfile::HashedRequirements
+        );
+        assert_eq!(
+            lockfile_kind(Path::new("requirements.txt"), "idna==3.7\n"),
+            Lockfile::Requirements
+        );
+    }
+
+    #[test]
+    fn test_pick_version() {
+        let versions = ["3.11".to_string(), "3.12".to_string()];
+        assert_eq!(pick_version(&versions, "1\n"), Some("3.11".to_string()));
+        assert_eq!(pick_version(&versions, " 2 "), Some("3.12".to_string()));
+        assert_eq!(pick_version(&versions, "3.12\n"), Some("3.12".to_string()));
+        assert_eq!(pick_version(&versions, "0"), None);
+        assert_eq!(pick_version(&versions, "3"), None);
+        assert_eq!(pick_version(&versions, "3.13"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_interpreter_link() {
+        let old_python = Some(Path::new("/old/pythons/3.12/python"));
+        let python = Path::new("/old/pythons/3.12/python/bin/python3.12");
+        assert!(is_interpreter_link(
+            Path::new("/venv/bin/python"),
+            python,
+            old_python
+        ));
+        assert!(is_interpreter_link(
+            Path::new("/venv/bin/python3.12"),
+            Path::new("/elsewhere/python3.12"),
+            None
+        ));
+        assert!(is_interpreter_link(
+            Path::new("/venv/bin/pydoc3"),
+            Path::new("/old/pythons/3.12/python/bin/pydoc3"),
+            old_python
+        ));
+        assert!(!is_interpreter_link(
+            Path::new("/venv/bin/tool"),
+            Path::new("/usr/local/bin/tool"),
+            old_python
+        ));
+        assert!(!is_interpreter_link(
+            Path::new("/venv/bin/python3"),
+            Path::new("python"),
+            old_python
+        ));
+    }
+
+    #[test]
+    fn test_repair_pyvenv_cfg() {
+        let config = "home = /old/pythons/3.12/python/bin
+include-system-site-packages = false
+version = 3.12.3
+executable = /old/pythons/3.12/python/bin/python3.12
+command = /old/pythons/3.12/python/bin/python3 -m venv /old/virtualenvs/foo/3.12
+";
+        let repaired = repair_pyvenv_cfg(
+            config,
+            Path::new("/new/pythons/3.12/python/bin/python3"),
+            Path::new("/new/pythons/3.12/python/bin/python3.12"),
+        );
+        assert_eq!(
+            repaired,
+            "home = /new/pythons/3.12/python/bin
+include-system-site-packages = false
+version = 3.12.3
+executable = /new/pythons/3.12/python/bin/python3.12
+command = /old/pythons/3.12/python/bin/python3 -m venv /old/virtualenvs/foo/3.12
+"
+        );
+    }
+
+    #[test]
+    fn test_activation_script() {
+        let version = "3.12".parse::<Version>().unwrap();
+        let env = [
+            ("VIRTUAL_ENV", "/venvs/it's/3.12".to_string()),
+            ("PATH", "/venvs/it's/3.12/bin:/usr/bin:/bin".to_string()),
+        ];
+        assert_eq!(
+            activation_script("it's", &version, &env),
+            r#"# Activates the it's 3.12 virtualenv. Generated by lilyenv; use with `. <file>`.
+if [ -n "${VIRTUAL_ENV+set}" ]; then _LILYENV_OLD_VIRTUAL_ENV="$VIRTUAL_ENV"; fi
+if [ -n "${PATH+set}" ]; then _LILYENV_OLD_PATH="$PATH"; fi
+export VIRTUAL_ENV='/venvs/it'\''s/3.12';
+export PATH='/venvs/it'\''s/3.12/bin':"$PATH";
+deactivate() {
+    if [ -n "${_LILYENV_OLD_VIRTUAL_ENV+set}" ]; then export VIRTUAL_ENV="$_LILYENV_OLD_VIRTUAL_ENV"; unset _LILYENV_OLD_VIRTUAL_ENV; else unset VIRTUAL_ENV; fi
+    if [ -n "${_LILYENV_OLD_PATH+set}" ]; then export PATH="$_LILYENV_OLD_PATH"; unset _LILYENV_OLD_PATH; else unset PATH; fi
+    unset -f deactivate
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_activation_script_interpreter_env() {
+        let version = "3.12".parse::<Version>().unwrap();
+        let env = [
+            (
+                LIBRARY_PATH_VARIABLE,
+                "/pythons/3.12/python/lib:/opt/lib".to_string(),
+            ),
+            (
+                "TERMINFO_DIRS",
+                "/home/me/.terminfo:/usr/share/terminfo".to_string(),
+            ),
+        ];
+        let script = activation_script("web", &version, &env);
+        let library_path = format!(
+            "export {LIBRARY_PATH_VARIABLE}='/pythons/3.12/python/lib'\"${{{LIBRARY_PATH_VARIABLE}:+:${LIBRARY_PATH_VARIABLE}}}\";\n"
+        );
+        assert!(script.contains(&library_path));
+        let terminfo_dirs = format!(
+            "export TERMINFO_DIRS=\"${{TERMINFO_DIRS:+$TERMINFO_DIRS:}}\"'{}';\n",
+            TERMINFO_CANDIDATES.join(":")
+        );
+        assert!(script.contains(&terminfo_dirs));
+        assert!(!script.contains("/opt/lib"));
+        assert!(!script.contains("/home/me/.terminfo"));
+    }
+
+    #[test]
+    fn test_virtualenv_env_library_path() {
+        let virtualenv = Path::new("/venvs/web/3.12");
+        let lib = Path::new("/pythons/3.12/python/lib");
+        let managed = virtualenv_env(virtualenv, Some(lib), String::new()).unwrap();
+        assert!(managed.contains(&(
+            LIBRARY_PATH_VARIABLE,
+            prepend_path(lib, std::env::var(LIBRARY_PATH_VARIABLE).ok().as_deref())
+        )));
+        let external = virtualenv_env(virtualenv, None, String::new()).unwrap();
+        assert!(!external
+            .iter()
+            .any(|(name, _)| *name == LIBRARY_PATH_VARIABLE));
+    }
+
+    #[test]
+    fn test_virtualenv_from_path() {
+        let virtualenvs = Path::new("/data/lilyenv/virtualenvs");
+        assert_eq!(
+            virtualenv_from_path(virtualenvs, Path::new("/data/lilyenv/virtualenvs/foo/3.12")),
+            Some(("foo".to_string(), "3.12".parse().unwrap()))
+        );
+        for path in [
+            "/data/lilyenv/virtualenvs/foo",
+            "/data/lilyenv/virtualenvs/foo/bar",
+            "/data/lilyenv/virtualenvs/foo/3.12/bin",
+            "/home/me/project/.venv",
+        ] {
+            assert_eq!(virtualenv_from_path(virtualenvs, Path::new(path)), None);
+        }
+    }
+
+    #[test]
+    fn test_prepend_path() {
+        let lib = Path::new("/pythons/3.12/python/lib");
+        assert_eq!(prepend_path(lib, None), "/pythons/3.12/python/lib");
+        assert_eq!(prepend_path(lib, Some("")), "/pythons/3.12/python/lib");
+        assert_eq!(
+            prepend_path(lib, Some("/opt/cuda/lib64:/usr/local/lib")),
+            "/pythons/3.12/python/lib:/opt/cuda/lib64:/usr/local/lib"
+        );
+    }
+
+    #[test]
+    fn test_venv_command() {
+        let python = Path::new("/pythons/3.12/python/bin/python3");
+        let virtualenv = Path::new("/virtualenvs/foo/3.12");
+        let args = ["--copies".to_string()];
+        let command = |backend| {
+            let command = venv_command(backend, python, &args, virtualenv);
+            let mut parts = vec![command.get_program().to_string_lossy().to_string()];
+            parts.extend(
+                command
+                    .get_args()
+                    .map(|arg| arg.to_string_lossy().to_string()),
+            );
+            parts.join(" ")
+        };
+        assert_eq!(
+            command(Backend::Venv),
+            "/pythons/3.12/python/bin/python3 -m venv --copies /virtualenvs/foo/3.12"
+        );
+        assert_eq!(
+            command(Backend::Virtualenv),
+            "virtualenv --python /pythons/3.12/python/bin/python3 --copies /virtualenvs/foo/3.12"
+        );
+        assert_eq!(
+            command(Backend::Uv),
+            "uv venv --seed --python /pythons/3.12/python/bin/python3 --copies /virtualenvs/foo/3.12"
+        );
+    }
+
+    #[test]
+    fn test_lookup_alias() {
+        let aliases = parse_aliases("w=work-service\n s = side-project\nbroken\nd=docs\nd=demo\n");
+        assert_eq!(
+            aliases,
+            vec![
+                ("w", "work-service"),
+                ("s", "side-project"),
+                ("d", "docs"),
+                ("d", "demo")
+            ]
+        );
+        assert_eq!(lookup_alias(&aliases, "w"), Some("work-service"));
+        assert_eq!(lookup_alias(&aliases, "s"), Some("side-project"));
+        assert_eq!(lookup_alias(&aliases, "work-service"), None);
+        assert_eq!(lookup_alias(&aliases, "d"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_venv_command_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = run_venv_command(
+            Backend::Venv,
+            Path::new("false"),
+            &[],
+            &dir.path().join("venv"),
+        );
+        assert!(matches!(result, Err(Error::CommandFailed(_))));
+    }
+
+    #[test]
+    fn test_replace_virtualenv() {
+        let dir = tempfile::tempdir().unwrap();
+        let virtualenv = dir.path().join("3.12");
+        std::fs::create_dir_all(&virtualenv).unwrap();
+        std::fs::write(virtualenv.join("pyvenv.cfg"), "old").unwrap();
+
+        let result = replace_virtualenv(&virtualenv, || {
+            std::fs::create_dir_all(&virtualenv)?;
+            std::fs::write(virtualenv.join("pyvenv.cfg"), "partial")?;
+            Err(Error::CommandFailed("python -m venv".to_string()))
+        });
+        assert!(matches!(result, Err(Error::CommandFailed(_))));
+        let config = std::fs::read_to_string(virtualenv.join("pyvenv.cfg")).unwrap();
+        assert_eq!(config, "old");
+
+        replace_virtualenv(&virtualenv, || {
+            std::fs::create_dir_all(&virtualenv)?;
+            std::fs::write(virtualenv.join("pyvenv.cfg"), "new")?;
+            Ok(())
+        })
+        .unwrap();
+        let config = std::fs::read_to_string(virtualenv.join("pyvenv.cfg")).unwrap();
+        assert_eq!(config, "new");
+        assert!(!dir.path().join("3.12.old").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_versions() {
+        use std::os::unix::ffi::OsStrExt;
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("3.12")).unwrap();
+        std::fs::create_dir(project.path().join("notes")).unwrap();
+        std::fs::create_dir(project.path().join(std::ffi::OsStr::from_bytes(b"3.\xff"))).unwrap();
+        std::fs::write(project.path().join("3.11"), "").unwrap();
+
+        let versions = list_versions(project.path().to_path_buf()).unwrap();
+        assert_eq!(versions, vec!["3.12"]);
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let version = "3.12".parse::<Version>().unwrap();
+        assert_eq!(
+            render_prompt(DEFAULT_PROMPT, "lilyenv", &version),
+            "lilyenv (3.12) "
+        );
+        assert_eq!(
+            render_prompt("[{version}] ", "lilyenv", &version),
+            "[3.12] "
+        );
+        assert_eq!(render_prompt("(venv) ", "lilyenv", &version), "(venv) ");
+    }
+}